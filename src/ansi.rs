@@ -0,0 +1,129 @@
+use std::ops::Range;
+
+/// The eight standard ANSI colors, in SGR order (30–37).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnsiColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl AnsiColor {
+    const ALL: [AnsiColor; 8] = [
+        AnsiColor::Black,
+        AnsiColor::Red,
+        AnsiColor::Green,
+        AnsiColor::Yellow,
+        AnsiColor::Blue,
+        AnsiColor::Magenta,
+        AnsiColor::Cyan,
+        AnsiColor::White,
+    ];
+}
+
+/// Text attributes set by SGR sequences. `fg: None` is the default color.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AnsiStyle {
+    pub fg: Option<AnsiColor>,
+    pub bold: bool,
+}
+
+impl AnsiStyle {
+    /// Applies the parameters of one `ESC [ ... m` sequence.
+    fn apply_sgr(&mut self, params: &str) {
+        for param in params.split(';') {
+            // An empty parameter means 0, so `ESC [ m` resets as well.
+            match param.parse::<u8>().unwrap_or(0) {
+                0 => *self = AnsiStyle::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                n @ 30..=37 => self.fg = Some(AnsiColor::ALL[usize::from(n - 30)]),
+                n @ 90..=97 => self.fg = Some(AnsiColor::ALL[usize::from(n - 90)]),
+                39 => self.fg = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Removes escape sequences from `text` and returns what is left together
+/// with the style of each run of it.
+///
+/// Only SGR (`ESC [ ... m`) changes the style; other CSI sequences are
+/// dropped. A sequence cut off at the end of `text` is dropped too, since the
+/// rest of it hasn't arrived yet. Runs cover the plain text without gaps.
+pub fn parse_ansi(text: &str) -> (String, Vec<(Range<usize>, AnsiStyle)>) {
+    let mut plain = String::with_capacity(text.len());
+    let mut runs = Vec::new();
+    let mut style = AnsiStyle::default();
+    let mut run_start = 0;
+    let mut rest = text;
+
+    while let Some(esc) = rest.find('\x1b') {
+        plain.push_str(&rest[..esc]);
+        rest = &rest[esc + 1..];
+        let Some(csi) = rest.strip_prefix('[') else {
+            continue;
+        };
+        let Some(end) = csi.find(|c: char| ('\x40'..='\x7e').contains(&c)) else {
+            rest = "";
+            break;
+        };
+        if csi[end..].starts_with('m') {
+            let mut next = style;
+            next.apply_sgr(&csi[..end]);
+            if next != style {
+                if plain.len() > run_start {
+                    runs.push((run_start..plain.len(), style));
+                }
+                run_start = plain.len();
+                style = next;
+            }
+        }
+        rest = &csi[end + 1..];
+    }
+    plain.push_str(rest);
+    if plain.len() > run_start {
+        runs.push((run_start..plain.len(), style));
+    }
+    (plain, runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sgr_splits_text_into_styled_runs() {
+        let (plain, runs) = parse_ansi("ok \x1b[1;31mfail\x1b[0m done");
+        assert_eq!(plain, "ok fail done");
+        let red = AnsiStyle {
+            fg: Some(AnsiColor::Red),
+            bold: true,
+        };
+        assert_eq!(
+            runs,
+            vec![
+                (0..3, AnsiStyle::default()),
+                (3..7, red),
+                (7..12, AnsiStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn other_and_cut_off_sequences_are_dropped() {
+        assert_eq!(parse_ansi("a\x1b[2Kb").0, "ab");
+        // The rest of the sequence arrives with the next chunk.
+        let (plain, runs) = parse_ansi("abc\x1b[3");
+        assert_eq!(plain, "abc");
+        assert_eq!(runs, vec![(0..3, AnsiStyle::default())]);
+        assert_eq!(parse_ansi("abc\x1b").0, "abc");
+        assert_eq!(parse_ansi("").1, vec![]);
+    }
+}
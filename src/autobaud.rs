@@ -0,0 +1,77 @@
+use crate::session::PortSettings;
+use std::io::{ErrorKind, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const COMMON_BAUD_RATES: [u32; 8] = [
+    9_600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// 'U' is 0b01010101, a clean square wave most autobaud detectors lock onto.
+pub const AUTOBAUD_CHAR: u8 = 0x55;
+
+pub enum AutobaudEvent {
+    /// What came back after sending the character at `baud_rate`.
+    Response {
+        baud_rate: u32,
+        data: Vec<u8>,
+    },
+    Failed {
+        baud_rate: u32,
+        error: String,
+    },
+    Done,
+}
+
+/// Sends `ch` at each rate in turn and collects whatever the device answers
+/// within `wait`. Stops at the first rate that gets a response.
+///
+/// The port is opened fresh for every rate, so it must not be in use.
+pub fn spawn_autobaud(
+    settings: PortSettings,
+    ch: u8,
+    rates: Vec<u32>,
+    wait: Duration,
+) -> Receiver<AutobaudEvent> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for baud_rate in rates {
+            let settings = PortSettings {
+                baud_rate,
+                ..settings.clone()
+            };
+            let event = match probe(&settings, ch, wait) {
+                Ok(data) => AutobaudEvent::Response { baud_rate, data },
+                Err(e) => AutobaudEvent::Failed {
+                    baud_rate,
+                    error: e.to_string(),
+                },
+            };
+            let answered =
+                matches!(&event, AutobaudEvent::Response { data, .. } if !data.is_empty());
+            if tx.send(event).is_err() || answered {
+                break;
+            }
+        }
+        let _ = tx.send(AutobaudEvent::Done);
+    });
+    rx
+}
+
+fn probe(settings: &PortSettings, ch: u8, wait: Duration) -> std::io::Result<Vec<u8>> {
+    let mut port = settings.open_port()?;
+    port.write_all(&[ch])?;
+
+    let deadline = Instant::now() + wait;
+    let mut data = Vec::new();
+    let mut buf = [0u8; 128];
+    while Instant::now() < deadline {
+        match port.read(&mut buf) {
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(data)
+}
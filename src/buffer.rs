@@ -0,0 +1,167 @@
+use crate::sync::lock;
+use chrono::{DateTime, Local};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Default for how much received data is kept for display.
+pub const DEFAULT_MAX_BYTES: usize = 1 << 20;
+
+/// The displayed side of an [`RxBuffer`].
+#[derive(Clone, Default)]
+pub struct RxData {
+    /// Raw bytes as received. Views decode them when drawing, so multi-byte
+    /// characters split across reads and binary data both survive intact.
+    pub bytes: Vec<u8>,
+    /// Arrival time of each line in `bytes`, in order.
+    pub line_times: Vec<DateTime<Local>>,
+    /// How many bytes have been trimmed off the front, i.e. the stream
+    /// offset of `bytes[0]`.
+    pub dropped: u64,
+}
+
+impl RxData {
+    /// Drops data from the front until at most `max` bytes are left. The cut
+    /// never lands inside a UTF-8 sequence, and a line cut in half keeps its
+    /// arrival time.
+    fn trim_to(&mut self, max: usize) {
+        let Some(mut cut) = self.bytes.len().checked_sub(max).filter(|&c| c > 0) else {
+            return;
+        };
+        while self.bytes.get(cut).is_some_and(|&b| b & 0xC0 == 0x80) {
+            cut += 1;
+        }
+        let whole_lines = self.bytes[..cut].iter().filter(|&&b| b == b'\n').count();
+        self.line_times
+            .drain(..whole_lines.min(self.line_times.len()));
+        self.bytes.drain(..cut);
+        self.dropped += cut as u64;
+    }
+
+    /// Every line of the data (with its terminator) next to when it started.
+    pub fn timed_lines(&self) -> impl Iterator<Item = (&DateTime<Local>, &[u8])> {
+        self.line_times
+            .iter()
+            .zip(self.bytes.split_inclusive(|&b| b == b'\n'))
+    }
+}
+
+/// Cuts received data into lines for [`RxBuffer::subscribe_lines`].
+#[derive(Default)]
+struct LineSplitter {
+    /// Data after the last newline.
+    partial: Vec<u8>,
+    taps: Vec<Sender<String>>,
+}
+
+impl LineSplitter {
+    /// Hands every line completed by `bytes` to the taps. A line longer than
+    /// `max` is passed on in pieces of that size.
+    fn feed(&mut self, bytes: &[u8], max: usize) {
+        for piece in bytes.split_inclusive(|&b| b == b'\n') {
+            self.partial.extend_from_slice(piece);
+            if self.partial.ends_with(b"\n") || self.partial.len() >= max {
+                let mut line = std::mem::take(&mut self.partial);
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                let line = String::from_utf8_lossy(&line).into_owned();
+                self.taps.retain(|tx| tx.send(line.clone()).is_ok());
+            }
+        }
+    }
+}
+
+/// Received data shared between the reader thread and whoever displays it.
+///
+/// Besides the displayed text, every chunk from the port is also handed to
+/// each subscriber, so scripts reading the stream never take data away from
+/// the display or from each other. Line subscribers get the same data cut
+/// into lines.
+///
+/// The displayed side is capped (see [`RxBuffer::set_max_bytes`]); the oldest
+/// data is dropped first.
+#[derive(Clone)]
+pub struct RxBuffer {
+    data: Arc<Mutex<RxData>>,
+    taps: Arc<Mutex<Vec<Sender<Vec<u8>>>>>,
+    lines: Arc<Mutex<LineSplitter>>,
+    max_bytes: Arc<AtomicUsize>,
+}
+
+impl Default for RxBuffer {
+    fn default() -> Self {
+        Self {
+            data: Arc::default(),
+            taps: Arc::default(),
+            lines: Arc::default(),
+            max_bytes: Arc::new(AtomicUsize::new(DEFAULT_MAX_BYTES)),
+        }
+    }
+}
+
+impl RxBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Sets how many bytes are kept for display and trims right away.
+    pub fn set_max_bytes(&self, max: usize) {
+        self.max_bytes.store(max, Ordering::Relaxed);
+        self.lock().trim_to(max);
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, RxData> {
+        lock(&self.data)
+    }
+
+    /// Appends a chunk to the displayed data only.
+    pub fn push_bytes(&self, bytes: &[u8]) {
+        let now = Local::now();
+        let mut data = self.lock();
+        for line in bytes.split_inclusive(|&b| b == b'\n') {
+            if data.bytes.last().is_none_or(|&b| b == b'\n') {
+                data.line_times.push(now);
+            }
+            data.bytes.extend_from_slice(line);
+        }
+        data.trim_to(self.max_bytes());
+    }
+
+    /// Appends a chunk straight from the port and passes it to subscribers.
+    pub fn receive(&self, bytes: &[u8]) {
+        self.push_bytes(bytes);
+        lock(&self.taps).retain(|tx| tx.send(bytes.to_vec()).is_ok());
+        lock(&self.lines).feed(bytes, self.max_bytes());
+    }
+
+    /// Returns a receiver that gets every chunk received from now on.
+    pub fn subscribe(&self) -> Receiver<Vec<u8>> {
+        let (tx, rx) = mpsc::channel();
+        lock(&self.taps).push(tx);
+        rx
+    }
+
+    /// Returns a receiver that gets every line received from now on, without
+    /// its line ending. A line that was already under way when subscribing
+    /// arrives in full.
+    pub fn subscribe_lines(&self) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        lock(&self.lines).taps.push(tx);
+        rx
+    }
+
+    pub fn clear(&self) {
+        let mut data = self.lock();
+        data.bytes.clear();
+        data.dropped = 0;
+        data.line_times.clear();
+    }
+}
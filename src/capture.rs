@@ -0,0 +1,200 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+impl Direction {
+    /// `←` for received data, `→` for sent data.
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Direction::Rx => "←",
+            Direction::Tx => "→",
+        }
+    }
+}
+
+/// One chunk of traffic as stored in a timestamped capture file.
+///
+/// On disk every entry is a line like
+/// `2026-10-15T12:03:44.512034+02:00 RX 48 65 6C 6C 6F`.
+#[derive(Clone, Debug)]
+pub struct CaptureEntry {
+    pub time: DateTime<FixedOffset>,
+    pub dir: Direction,
+    pub data: Vec<u8>,
+}
+
+impl CaptureEntry {
+    pub fn to_line(&self) -> String {
+        let dir = match self.dir {
+            Direction::Rx => "RX",
+            Direction::Tx => "TX",
+        };
+        let hex: Vec<String> = self.data.iter().map(|b| format!("{:02X}", b)).collect();
+        format!(
+            "{} {} {}",
+            self.time.to_rfc3339_opts(SecondsFormat::Micros, false),
+            dir,
+            hex.join(" ")
+        )
+    }
+
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let time = DateTime::parse_from_rfc3339(parts.next()?).ok()?;
+        let dir = match parts.next()? {
+            "RX" => Direction::Rx,
+            "TX" => Direction::Tx,
+            _ => return None,
+        };
+        let data = parts
+            .map(|b| u8::from_str_radix(b, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Self { time, dir, data })
+    }
+}
+
+/// Reads a capture file, failing on the first line that isn't an entry.
+pub fn read_capture(path: &Path) -> io::Result<Vec<CaptureEntry>> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            CaptureEntry::parse_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: not a capture entry", i + 1),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Whether the first non-empty line of the file at `path` is a capture
+/// entry, i.e. the file was meant to be a capture even if later lines are
+/// broken.
+pub fn is_capture(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|bytes| {
+        bytes
+            .split(|&b| b == b'\n')
+            .map(String::from_utf8_lossy)
+            .find(|line| !line.trim().is_empty())
+            .is_some_and(|line| CaptureEntry::parse_line(&line).is_some())
+    })
+}
+
+/// The data of the entries going in `dir`, each paired with how long after
+/// the first of them it was captured.
+pub fn timed_chunks(entries: Vec<CaptureEntry>, dir: Direction) -> Vec<(Duration, Vec<u8>)> {
+    let mut start = None;
+    entries
+        .into_iter()
+        .filter(|e| e.dir == dir)
+        .map(|e| {
+            let start = *start.get_or_insert(e.time);
+            let offset = (e.time - start).to_std().unwrap_or_default();
+            (offset, e.data)
+        })
+        .collect()
+}
+
+/// Plays the received side of a capture back on a virtual clock.
+pub struct Replay {
+    entries: Vec<CaptureEntry>,
+    offsets: Vec<Duration>,
+    position: Duration,
+    next: usize,
+}
+
+impl Replay {
+    /// Keeps only the RX entries; their timing is taken relative to the first.
+    pub fn new(entries: Vec<CaptureEntry>) -> Self {
+        let entries: Vec<CaptureEntry> = entries
+            .into_iter()
+            .filter(|e| e.dir == Direction::Rx)
+            .collect();
+        let start = entries.first().map(|e| e.time);
+        let offsets = entries
+            .iter()
+            .map(|e| {
+                start
+                    .and_then(|s| (e.time - s).to_std().ok())
+                    .unwrap_or_default()
+            })
+            .collect();
+        Self {
+            entries,
+            offsets,
+            position: Duration::ZERO,
+            next: 0,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.offsets.last().copied().unwrap_or_default()
+    }
+
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next == self.entries.len()
+    }
+
+    /// Moves the clock forward by `elapsed` and returns the entries that
+    /// became due.
+    pub fn advance(&mut self, elapsed: Duration) -> &[CaptureEntry] {
+        self.position = (self.position + elapsed).min(self.duration());
+        let from = self.next;
+        while self.next < self.entries.len() && self.offsets[self.next] <= self.position {
+            self.next += 1;
+        }
+        &self.entries[from..self.next]
+    }
+
+    /// Jumps to `position` and returns every entry up to it, so the display
+    /// can be rebuilt from scratch.
+    pub fn seek(&mut self, position: Duration) -> &[CaptureEntry] {
+        self.position = Duration::ZERO;
+        self.next = 0;
+        self.advance(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_line_round_trips() {
+        let line = "2026-10-15T12:03:44.512034+02:00 TX 48 65 0A";
+        let entry = CaptureEntry::parse_line(line).unwrap();
+        assert_eq!(entry.dir, Direction::Tx);
+        assert_eq!(entry.data, b"He\n");
+        assert_eq!(entry.to_line(), line);
+
+        let empty = CaptureEntry {
+            data: Vec::new(),
+            ..entry
+        };
+        let parsed = CaptureEntry::parse_line(&empty.to_line()).unwrap();
+        assert_eq!(parsed.time, empty.time);
+        assert!(parsed.data.is_empty());
+    }
+
+    #[test]
+    fn other_lines_are_not_entries() {
+        assert!(CaptureEntry::parse_line("hello world").is_none());
+        assert!(CaptureEntry::parse_line("2026-10-15T12:03:44+02:00 XX 48").is_none());
+        assert!(CaptureEntry::parse_line("2026-10-15T12:03:44+02:00 RX 4G").is_none());
+    }
+}
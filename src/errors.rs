@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCategory {
+    Open,
+    Read,
+    Write,
+    Framing,
+    /// Bytes missing from a counter test stream.
+    Dropped,
+    Script,
+}
+
+impl ErrorCategory {
+    pub const ALL: [ErrorCategory; 6] = [
+        ErrorCategory::Open,
+        ErrorCategory::Read,
+        ErrorCategory::Write,
+        ErrorCategory::Framing,
+        ErrorCategory::Dropped,
+        ErrorCategory::Script,
+    ];
+
+    /// Whether the counter counts occurrences. [`ErrorCategory::Dropped`]
+    /// counts bytes instead, so it stays out of [`ErrorCounters::total`].
+    pub fn is_event(self) -> bool {
+        self != ErrorCategory::Dropped
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ErrorCategory::Open => "Open failures",
+            ErrorCategory::Read => "Read errors",
+            ErrorCategory::Write => "Write errors",
+            ErrorCategory::Framing => "Framing/parity errors",
+            ErrorCategory::Dropped => "Dropped bytes",
+            ErrorCategory::Script => "Script errors",
+        }
+    }
+}
+
+/// Per-category error counts, shared by every thread that can hit an error.
+#[derive(Clone, Default)]
+pub struct ErrorCounters(Arc<[AtomicU64; ErrorCategory::ALL.len()]>);
+
+impl ErrorCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, category: ErrorCategory, n: u64) {
+        self.0[category as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, category: ErrorCategory) -> u64 {
+        self.0[category as usize].load(Ordering::Relaxed)
+    }
+
+    /// All errors that happened, not counting dropped bytes.
+    pub fn total(&self) -> u64 {
+        ErrorCategory::ALL
+            .iter()
+            .filter(|c| c.is_event())
+            .map(|&c| self.get(c))
+            .sum()
+    }
+
+    pub fn reset(&self) {
+        for counter in self.0.iter() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
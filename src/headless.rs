@@ -0,0 +1,191 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use uart_debug::errors::ErrorCounters;
+use uart_debug::logging;
+use uart_debug::script::PortStatus;
+use uart_debug::session::open_error_message;
+use uart_debug::timeline::Timeline;
+use uart_debug::traffic::Traffic;
+use uart_debug::{PortRequest, PortSettings, RxBuffer, ScriptHost, SerialSession, WndOp, script};
+
+pub const USAGE: &str = "usage: uart-debug [--script <path.rhai> [--port <name>] [--baud <rate>]]";
+
+// Exit codes: scripts that pass exit with 0, failed assertions with 1 and
+// anything that kept the script from running to completion with 2.
+const EXIT_FAILED: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+/// Used when `--baud` isn't given.
+const DEFAULT_BAUD: u32 = 115_200;
+
+/// How long sends may wait to be passed on while no port operation comes in.
+const SEND_POLL: Duration = Duration::from_millis(1);
+
+/// How long the script's last sends may take to go out once it has ended.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Args {
+    script: PathBuf,
+    port: Option<String>,
+    baud: u32,
+}
+
+/// Returns `Ok(None)` when no `--script` was given, meaning the GUI should start.
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut script = None;
+    let mut port = None;
+    let mut baud = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--script" => script = Some(PathBuf::from(value()?)),
+            "--port" => port = Some(value()?),
+            "--baud" => {
+                let v = value()?;
+                baud = Some(v.parse().map_err(|_| format!("invalid baud rate: {}", v))?);
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+
+    match script {
+        Some(script) => Ok(Some(Args {
+            script,
+            port,
+            baud: baud.unwrap_or(DEFAULT_BAUD),
+        })),
+        None if port.is_some() => Err("--port requires --script".to_string()),
+        None if baud.is_some() => Err("--baud requires --script".to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Runs the script to completion without a window and returns the exit code.
+pub fn run(args: &Args) -> i32 {
+    logging::init(None, true);
+    let rx_buffer = RxBuffer::new();
+    let session = match &args.port {
+        Some(name) => {
+            let settings = PortSettings::new(name.clone(), args.baud);
+            match SerialSession::open(
+                &settings,
+                rx_buffer.clone(),
+                Traffic::new(),
+                Timeline::new(),
+            ) {
+                Ok(session) => Some(Arc::new(session)),
+                Err(e) => {
+                    eprintln!("{}", open_error_message(name, &e));
+                    return EXIT_ERROR;
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Script windows have nowhere to go, so their text ends up on stdout.
+    let (wnd_tx, wnd_rx) = mpsc::channel::<WndOp>();
+    let clear_buffer = rx_buffer.clone();
+    let printer = thread::spawn(move || {
+        let mut names = Vec::new();
+        while let Ok(op) = wnd_rx.recv() {
+            match op {
+                WndOp::New(id, name) => names.push((id, name)),
+                WndOp::WriteText(id, text) => {
+                    let name = names
+                        .iter()
+                        .find(|(i, _)| *i == id)
+                        .map(|(_, n)| n.as_str());
+                    print!("[{}] {}", name.unwrap_or("?"), text);
+                }
+                WndOp::Close(id) => names.retain(|(i, _)| *i != id),
+                WndOp::ClearRx => clear_buffer.clear(),
+                WndOp::SetFont(..) | WndOp::SetLimit(..) => {}
+            }
+        }
+    });
+
+    // Sends and line control share one thread, so an operation like a flush
+    // always comes after the data the script sent before it.
+    let (port_tx, port_rx) = mpsc::channel::<PortRequest>();
+    let (uart_tx, send_rx) = mpsc::channel::<Vec<u8>>();
+    let port_session = session.clone();
+    let controller = thread::spawn(move || {
+        let send_pending = || {
+            for data in send_rx.try_iter() {
+                match &port_session {
+                    Some(session) => session.write(&data),
+                    None => eprintln!("uart_send ignored: no --port given"),
+                }
+            }
+        };
+        loop {
+            send_pending();
+            let request = match port_rx.recv_timeout(SEND_POLL) {
+                Ok(request) => request,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            send_pending();
+            let result = match &port_session {
+                Some(session) => session.apply(request.op.clone()).map_err(|e| e.to_string()),
+                None => Err("no --port given".to_string()),
+            };
+            if let Err(e) = &result {
+                eprintln!("Port operation failed: {}", e);
+            }
+            request.finish(result);
+        }
+        // The script is done; whatever it sent last still goes out.
+        send_pending();
+    });
+
+    let (status_tx, status_rx) = mpsc::channel::<mpsc::Sender<PortStatus>>();
+    let status_session = session.clone();
+    let reporter = thread::spawn(move || {
+        while let Ok(reply) = status_rx.recv() {
+            let _ = reply.send(PortStatus::of(status_session.as_deref()));
+        }
+    });
+
+    let host = ScriptHost {
+        wnd_tx,
+        port_tx,
+        uart_tx,
+        status_tx,
+        rx_buffer,
+        errors: ErrorCounters::new(),
+    };
+    let failures = Arc::new(AtomicUsize::new(0));
+    let engine = script::new_engine(&host, Arc::default(), Arc::clone(&failures));
+    let result = engine.run_file(args.script.clone());
+    // Dropping every sender lets the helper threads finish.
+    drop(engine);
+    drop(host);
+    let _ = printer.join();
+    let _ = controller.join();
+    let _ = reporter.join();
+    // The helpers held the other references, so the session is ours again.
+    if let Some(session) = session.and_then(Arc::into_inner)
+        && let Err(e) = session.close(CLOSE_TIMEOUT)
+    {
+        eprintln!("Closing the port: {}", e);
+    }
+
+    if let Err(e) = result {
+        eprintln!("Rhai Error: {}", e);
+        return EXIT_ERROR;
+    }
+    match failures.load(Ordering::Relaxed) {
+        0 => 0,
+        n => {
+            eprintln!("{} assertion(s) failed", n);
+            EXIT_FAILED
+        }
+    }
+}
@@ -0,0 +1,66 @@
+/// Parses whitespace-separated hex bytes such as `0A FF 7E`.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    text.split_whitespace()
+        .map(|pair| {
+            if pair.len() != 2 {
+                return Err(format!("'{}' is not a two-digit hex byte", pair));
+            }
+            // from_str_radix would also take a sign, as in `+1`.
+            if !pair.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(format!("'{}' is not valid hex", pair));
+            }
+            u8::from_str_radix(pair, 16).map_err(|_| format!("'{}' is not valid hex", pair))
+        })
+        .collect()
+}
+
+/// Turns text with the escapes `\n`, `\r`, `\t`, `\0`, `\\` and `\xNN` into
+/// bytes.
+pub fn parse_escaped(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                let byte = (digits.len() == 2)
+                    .then(|| u8::from_str_radix(&digits, 16).ok())
+                    .flatten()
+                    .ok_or_else(|| format!("'\\x{}' is not a two-digit hex byte", digits))?;
+                bytes.push(byte);
+            }
+            Some(other) => return Err(format!("unknown escape '\\{}'", other)),
+            None => return Err("trailing '\\'".to_string()),
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_takes_pairs_in_any_case() {
+        assert_eq!(parse_hex(" 0a FF\t7e "), Ok(vec![0x0A, 0xFF, 0x7E]));
+        assert_eq!(parse_hex(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn parse_hex_rejects_odd_and_invalid_digits() {
+        assert!(parse_hex("A").is_err());
+        assert!(parse_hex("ABC").is_err());
+        assert!(parse_hex("0G").is_err());
+        assert!(parse_hex("+1").is_err());
+    }
+}
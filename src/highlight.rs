@@ -0,0 +1,50 @@
+use std::ops::Range;
+
+/// Finds every occurrence of the keywords in `text`.
+///
+/// Returns byte ranges sorted by position, each tagged with the index of the
+/// keyword that matched. Overlapping matches keep the one starting first
+/// (ties go to the earlier keyword) and empty keywords never match.
+pub fn find_keywords<S: AsRef<str>>(text: &str, keywords: &[S]) -> Vec<(Range<usize>, usize)> {
+    let mut matches: Vec<(Range<usize>, usize)> = keywords
+        .iter()
+        .enumerate()
+        .filter(|(_, k)| !k.as_ref().is_empty())
+        .flat_map(|(i, k)| {
+            let len = k.as_ref().len();
+            text.match_indices(k.as_ref())
+                .map(move |(start, _)| (start..start + len, i))
+        })
+        .collect();
+    matches.sort_by_key(|(range, i)| (range.start, *i));
+
+    let mut end = 0;
+    matches.retain(|(range, _)| {
+        let keep = range.start >= end;
+        if keep {
+            end = range.end;
+        }
+        keep
+    });
+    matches
+}
+
+/// Finds every non-overlapping occurrence of `query` in `text`, optionally
+/// ignoring ASCII case. An empty query never matches.
+pub fn find_all(text: &str, query: &str, ignore_case: bool) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let matches = |text: &str, query: &str| -> Vec<Range<usize>> {
+        text.match_indices(query)
+            .map(|(start, m)| start..start + m.len())
+            .collect()
+    };
+    if ignore_case {
+        // ASCII lowercasing keeps every byte where it is, so the offsets
+        // still fit `text`.
+        matches(&text.to_ascii_lowercase(), &query.to_ascii_lowercase())
+    } else {
+        matches(text, query)
+    }
+}
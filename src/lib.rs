@@ -0,0 +1,27 @@
+//! Core of the UART debug tool: serial transport, receive buffering and Rhai
+//! scripting, independent of the egui frontend in `main.rs`.
+
+pub mod ansi;
+pub mod autobaud;
+pub mod buffer;
+pub mod capture;
+pub mod errors;
+pub mod hex;
+pub mod highlight;
+pub mod line_ending;
+pub mod logging;
+pub mod pattern;
+pub mod render;
+pub mod script;
+pub mod session;
+mod sync;
+pub mod timeline;
+pub mod timestamp;
+pub mod traffic;
+pub mod transfer;
+pub mod transport;
+pub mod trigger;
+
+pub use buffer::RxBuffer;
+pub use script::{PortRequest, ScriptHost, WndOp};
+pub use session::{PortOp, PortSettings, SerialSession, SessionEvent};
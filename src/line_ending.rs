@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+            LineEnding::Cr => b"\r",
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+        })
+    }
+}
+
+/// Counts line endings in a received stream, chunk by chunk.
+#[derive(Default)]
+pub struct LineEndingStats {
+    lf: usize,
+    crlf: usize,
+    cr: usize,
+    // A CR at the end of the last chunk may still turn out to be a CRLF.
+    pending_cr: bool,
+}
+
+impl LineEndingStats {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            match (self.pending_cr, b) {
+                (true, b'\n') => self.crlf += 1,
+                (true, _) => self.cr += 1,
+                (false, b'\n') => self.lf += 1,
+                _ => {}
+            }
+            self.pending_cr = b == b'\r';
+        }
+    }
+
+    /// Short label for the status bar: the convention seen so far, "mixed"
+    /// when several kinds showed up, or "unknown" before the first one.
+    pub fn summary(&self) -> String {
+        let seen = [
+            (self.lf, LineEnding::Lf),
+            (self.crlf, LineEnding::CrLf),
+            (self.cr, LineEnding::Cr),
+        ];
+        let mut kinds = seen.iter().filter(|(n, _)| *n > 0);
+        match (kinds.next(), kinds.next()) {
+            (None, _) => "unknown".to_string(),
+            (Some((_, ending)), None) => ending.to_string(),
+            _ => "mixed".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crlf_split_across_chunks_counts_once() {
+        let mut stats = LineEndingStats::default();
+        assert_eq!(stats.summary(), "unknown");
+        stats.feed(b"one\r");
+        stats.feed(b"\ntwo\r\n");
+        assert_eq!((stats.lf, stats.crlf, stats.cr), (0, 2, 0));
+        assert_eq!(stats.summary(), "CRLF");
+    }
+
+    #[test]
+    fn different_endings_are_mixed() {
+        let mut stats = LineEndingStats::default();
+        stats.feed(b"a\r\rb\n");
+        assert_eq!((stats.lf, stats.crlf, stats.cr), (1, 0, 2));
+        assert_eq!(stats.summary(), "mixed");
+    }
+}
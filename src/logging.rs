@@ -0,0 +1,164 @@
+use crate::sync::lock;
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Once the log file grows past this it is moved to `<name>.1` and a new one
+/// is started.
+const MAX_FILE_BYTES: u64 = 1 << 20;
+/// Most lines kept by [`RecentLog`].
+const RECENT_LINES: usize = 200;
+const FILE_NAME: &str = "uart-debug.log";
+/// Module path prefix of this crate's own records. Dependencies such as the
+/// windowing and graphics stack only get through from `Warn` up.
+const OWN_TARGET: &str = "uart_debug";
+
+/// The latest log lines, for showing in the UI.
+#[derive(Clone, Default)]
+pub struct RecentLog(Arc<Mutex<VecDeque<String>>>);
+
+impl RecentLog {
+    pub fn lines(&self) -> Vec<String> {
+        lock(&self.0).iter().cloned().collect()
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = lock(&self.0);
+        if lines.len() == RECENT_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// A log file that starts over once it reaches [`MAX_FILE_BYTES`], keeping
+/// the previous one as a backup.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { path, file, len })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.len >= MAX_FILE_BYTES {
+            let mut backup = self.path.clone().into_os_string();
+            backup.push(".1");
+            fs::rename(&self.path, backup)?;
+            *self = Self::open(self.path.clone())?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.len += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Sends every record to the log file if there is one and the [`RecentLog`],
+/// and to the console when `console` is set.
+struct Logger {
+    file: Option<Mutex<RotatingFile>>,
+    recent: RecentLog,
+    console: bool,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(OWN_TARGET) || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.args()
+        );
+        if self.console {
+            if record.level() <= Level::Warn {
+                eprintln!("{}", record.args());
+            } else {
+                println!("{}", record.args());
+            }
+        }
+        if let Some(file) = &self.file {
+            let _ = lock(file).write_line(&line);
+        }
+        self.recent.push(line);
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = lock(file).file.flush();
+        }
+    }
+}
+
+/// Where the GUI keeps its log: next to the executable, or in the temporary
+/// directory if that isn't writable.
+pub fn default_log_path() -> PathBuf {
+    let beside_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(FILE_NAME)));
+    match beside_exe {
+        Some(path) if is_writable(&path) => path,
+        _ => std::env::temp_dir().join(FILE_NAME),
+    }
+}
+
+fn is_writable(path: &Path) -> bool {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .is_ok()
+}
+
+/// Installs the logger, writing to `file` when given and to the console when
+/// `console` is set, and has panics logged before they unwind. Call once, at
+/// startup.
+pub fn init(file: Option<PathBuf>, console: bool) -> RecentLog {
+    let recent = RecentLog::default();
+    let mut open_error = None;
+    let file = file.and_then(|path| match RotatingFile::open(path.clone()) {
+        Ok(file) => Some(Mutex::new(file)),
+        Err(e) => {
+            open_error = Some(format!("cannot open log file {}: {}", path.display(), e));
+            None
+        }
+    });
+    let logger = Logger {
+        file,
+        recent: recent.clone(),
+        console,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(if cfg!(debug_assertions) {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        });
+    }
+    if let Some(e) = open_error {
+        log::warn!("{}", e);
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        default_hook(info);
+    }));
+    recent
+}
@@ -1,22 +1,62 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use eframe::egui::{Color32, ComboBox, EventFilter};
+use chrono::{DateTime, Local};
+use eframe::egui::text::{CCursor, LayoutJob, TextFormat};
+use eframe::egui::{Color32, ComboBox, EventFilter, FontId};
 use eframe::{App, egui};
-use rhai::Engine;
-use serialport::{Parity, SerialPort, SerialPortInfo, StopBits};
+use serde::{Deserialize, Serialize};
+use serialport::{DataBits, FlowControl, Parity, SerialPortInfo, StopBits};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::io::{BufReader, Cursor};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use std::{io::Read, thread};
+use std::time::{Duration, Instant};
+use uart_debug::ansi::{self, AnsiColor, AnsiStyle};
+use uart_debug::autobaud::{self, AutobaudEvent};
+use uart_debug::buffer::{DEFAULT_MAX_BYTES, RxData};
+use uart_debug::capture::{self, Direction, Replay};
+use uart_debug::errors::{ErrorCategory, ErrorCounters};
+use uart_debug::hex::{parse_escaped, parse_hex};
+use uart_debug::highlight::{find_all, find_keywords};
+use uart_debug::line_ending::{LineEnding, LineEndingStats};
+use uart_debug::logging::{self, RecentLog};
+use uart_debug::pattern::{self, CounterWidth, SequenceChecker};
+use uart_debug::render::{
+    CarriageReturn, Encoding, NonPrintable, apply_carriage_returns, decode, hex_dump, render_ascii,
+    xxd_dump,
+};
+use uart_debug::session::{
+    DEFAULT_READ_CHUNK, LogFile, LogFormat, MAX_READ_CHUNK, MIN_READ_CHUNK, Writer,
+    open_error_message,
+};
+use uart_debug::timeline::Timeline;
+use uart_debug::timestamp::{TimestampMode, format_stamp};
+use uart_debug::traffic::{RateMeter, Traffic};
+use uart_debug::transfer::{self, HandshakeConfig, TransferEvent};
+use uart_debug::transport::TransportKind;
+use uart_debug::trigger::{self, Fired, Trigger, TriggerAction, TriggerSinks, TriggerTable};
+use uart_debug::{
+    PortOp, PortRequest, PortSettings, RxBuffer, ScriptHost, SerialSession, SessionEvent, WndOp,
+    script::{self, PortStatus, ScriptEvent},
+};
 
-#[derive(Debug, PartialEq)]
+mod headless;
+
+/// How many sent Debug inputs are kept for recall.
+const TX_HISTORY_LEN: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 enum Mode {
     Terminal,
     Debug,
+    /// Sent and received lines interleaved.
+    Timeline,
 }
 
 #[derive(Default)]
@@ -24,28 +64,771 @@ struct Window {
     id: usize,
     name: String,
     text: String,
+    monospace: bool,
+    /// Most bytes of text kept; the oldest text is dropped first.
+    max_len: usize,
+}
+
+/// Default text limit of a script window.
+const WINDOW_TEXT_LIMIT: usize = 64 * 1024;
+
+impl Window {
+    fn append(&mut self, text: &str) {
+        self.text += text;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if let Some(excess) = self.text.len().checked_sub(self.max_len) {
+            let mut cut = excess;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+        }
+    }
 }
 
-enum WndOp {
-    New(usize, String),
-    WriteText(usize, String),
-    Close(usize),
+struct Highlight {
+    keyword: String,
+    color: Color32,
 }
 
-struct UartApp {
+/// A button in the Debug view that sends a fixed payload.
+#[derive(Clone, Serialize, Deserialize)]
+struct Macro {
+    label: String,
+    payload: String,
+    /// `payload` is hex bytes rather than text.
+    hex: bool,
+}
+
+impl Macro {
+    fn bytes(&self) -> Result<Vec<u8>, String> {
+        if self.hex {
+            parse_hex(&self.payload)
+        } else {
+            Ok(self.payload.as_bytes().to_vec())
+        }
+    }
+}
+
+/// Plain Terminal text on the dark and the light theme.
+const TERMINAL_TEXT_DARK: Color32 = Color32::ORANGE;
+const TERMINAL_TEXT_LIGHT: Color32 = Color32::from_rgb(0xA0, 0x40, 0x00);
+
+/// Sent lines in the Timeline view on the dark and the light theme.
+const TIMELINE_TX_DARK: Color32 = Color32::LIGHT_BLUE;
+const TIMELINE_TX_LIGHT: Color32 = Color32::from_rgb(0x00, 0x40, 0xA0);
+
+/// Backgrounds for the selected search hit and for all the others.
+const SEARCH_CURRENT: Color32 = Color32::from_rgb(230, 120, 0);
+const SEARCH_OTHER: Color32 = Color32::from_rgb(110, 90, 0);
+
+// Background colors handed out to new highlights in turn.
+const HIGHLIGHT_PALETTE: [Color32; 4] = [
+    Color32::DARK_RED,
+    Color32::DARK_GREEN,
+    Color32::DARK_BLUE,
+    Color32::from_rgb(120, 90, 0),
+];
+
+struct ReplayState {
+    replay: Replay,
+    playing: bool,
+    speed: f32,
+    last_tick: Instant,
+}
+
+struct AutobaudState {
+    open: bool,
+    ch: u8,
+    cycle: bool,
+    events: Option<Receiver<AutobaudEvent>>,
+    log: Vec<String>,
+    answered_at: Option<u32>,
+}
+
+impl Default for AutobaudState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            ch: autobaud::AUTOBAUD_CHAR,
+            cycle: true,
+            events: None,
+            log: Vec::new(),
+            answered_at: None,
+        }
+    }
+}
+
+const APP_TITLE: &str = "UART Debug Tool";
+
+struct CounterTest {
+    open: bool,
+    width: CounterWidth,
+    /// Frames to send; zero keeps going until stopped.
+    count: usize,
+    sending: bool,
+    next: u64,
+    sent: usize,
+    last_tick: Instant,
+    checker: Option<SequenceChecker>,
+}
+
+impl Default for CounterTest {
+    fn default() -> Self {
+        Self {
+            open: false,
+            width: CounterWidth::U8,
+            count: 0,
+            sending: false,
+            next: 0,
+            sent: 0,
+            last_tick: Instant::now(),
+            checker: None,
+        }
+    }
+}
+
+struct HandshakeSend {
+    open: bool,
+    block_size: usize,
+    ack: String,
+    ack_hex: bool,
+    timeout_ms: u64,
+    retries: u32,
+    transfer: PacedSend,
+}
+
+impl Default for HandshakeSend {
+    fn default() -> Self {
+        Self {
+            open: false,
+            block_size: 256,
+            ack: "06".to_string(),
+            ack_hex: true,
+            timeout_ms: 1000,
+            retries: 3,
+            transfer: PacedSend::default(),
+        }
+    }
+}
+
+/// Storage key for [`Config`].
+const CONFIG_KEY: &str = "config";
+
+/// The settings kept between runs. Fields missing from a config saved by an
+/// older version keep their defaults, so upgrading keeps the rest.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct Config {
     mode: Mode,
-    ports: Vec<SerialPortInfo>,
-    selected_port: Option<usize>,
+    transport: TransportKind,
+    /// Restored by name, since port indices change between runs.
+    port_name: Option<String>,
+    tcp_address: String,
+    baud_rate: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+    flow_control: FlowControl,
+    flush_interval_ms: u64,
+    read_timeout_ms: u64,
+    poll_interval_ms: u64,
+    read_chunk: usize,
+    rx_max_bytes: usize,
+    auto_reconnect: bool,
+    clear_on_connect: bool,
+    break_ms: u64,
+    tx_hex: bool,
+    tx_line_ending: Option<LineEnding>,
+    send_delay_ms: u64,
+    echo_tx: bool,
+    local_echo: bool,
+    non_printable: NonPrintable,
+    wrap_ascii: bool,
+    encoding: Encoding,
+    carriage_return: CarriageReturn,
+    auto_scroll: bool,
+    show_ascii: bool,
+    show_hex: bool,
+    split: f32,
+    hex_columns: usize,
+    font_size: f32,
+    dark_mode: bool,
+    timestamps: bool,
+    timestamp_mode: TimestampMode,
+    script_dir: Option<PathBuf>,
+    firmware_dir: Option<PathBuf>,
+    macros: Vec<Macro>,
+    triggers: Vec<Trigger>,
+    window_rects: HashMap<String, egui::Rect>,
+    log_format: LogFormat,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        UartApp::default().config()
+    }
+}
+
+/// Retrying a port that went away, until it can be opened again.
+struct Reconnect {
+    settings: PortSettings,
+    attempts: u32,
+    next_try: Instant,
+}
+
+/// Connects or disconnects from anywhere in the window.
+const CONNECT_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::K);
+const CONNECT_SHORTCUT_HINT: &str = "Ctrl+K (⌘K on macOS)";
+
+/// How long closing the app waits for each port's pending writes.
+const EXIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Shown when something is typed or sent without an open port.
+const NOT_CONNECTED: &str = "Not connected — connect to a port first";
+
+/// A label at a point in the received data.
+struct Marker {
+    /// Stream offset, counting bytes dropped from the front of the buffer.
+    offset: u64,
+    label: String,
+    kind: MarkerKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MarkerKind {
+    /// Placed by the user.
+    Label,
+    /// Bytes sent while Echo TX is on, shown where they went out.
+    TxEcho,
+    /// A framing, parity or overrun error reported by the port.
+    LineError,
+    /// Text typed in the Terminal view while Local echo is on. The Terminal
+    /// view shows it inline; the label is the text itself.
+    LocalEcho,
+}
+
+/// Background of marker lines in the ASCII view.
+const MARKER_BACKGROUND: Color32 = Color32::from_rgb(0, 90, 140);
+/// Background of echoed TX lines in the ASCII view.
+const TX_ECHO_BACKGROUND: Color32 = Color32::from_rgb(40, 90, 40);
+/// Background of line error markers in the ASCII view.
+const LINE_ERROR_BACKGROUND: Color32 = Color32::from_rgb(140, 30, 30);
+
+/// Most entries kept in the Script output panel.
+const SCRIPT_OUTPUT_LINES: usize = 200;
+
+/// One line of the Script output panel.
+struct ScriptOutput {
+    text: String,
+    failed: bool,
+}
+
+/// Most entries kept in the trigger log.
+const TRIGGER_LOG_LINES: usize = 100;
+
+/// Clearing more received data than this asks first.
+const CLEAR_CONFIRM_BYTES: usize = 256 * 1024;
+
+/// A script that is run again every `script_every_s` seconds.
+struct ScriptRepeat {
+    path: PathBuf,
+    next_run: Instant,
+}
+
+/// egui's own monospace size.
+const DEFAULT_FONT_SIZE: f32 = 12.0;
+
+/// Time between reconnect attempts.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Block size and pause used by "program device".
+const PROGRAM_BLOCK: usize = 512;
+const PROGRAM_GAP: Duration = Duration::from_millis(10);
+
+/// A file sent in blocks on a background thread, as used by "program
+/// device", "send file", "replay to port" and the handshake send.
+#[derive(Default)]
+struct PacedSend {
+    /// Set while a file is being sent.
+    events: Option<Receiver<TransferEvent>>,
+    abort: Arc<AtomicBool>,
+    /// [`Connection::id`] of the connection it was started on.
+    conn: usize,
+    file_name: String,
+    progress: f32,
+    result: String,
+}
+
+impl PacedSend {
+    fn start(&mut self, path: &Path, block_size: usize, gap: Duration, writer: Writer) {
+        let abort = self.restart(path);
+        match File::open(path).and_then(|f| Ok((f.metadata()?.len() as usize, f))) {
+            Ok((total, file)) => {
+                self.events = Some(transfer::spawn_paced_send(
+                    BufReader::new(file),
+                    total,
+                    block_size,
+                    gap,
+                    writer,
+                    abort,
+                ));
+            }
+            Err(e) => self.result = format!("Cannot open {}: {}", path.display(), e),
+        }
+    }
+
+    /// Sends `data`, already loaded from `path`, like [`Self::start`].
+    fn start_data(
+        &mut self,
+        path: &Path,
+        data: Vec<u8>,
+        block_size: usize,
+        gap: Duration,
+        writer: Writer,
+    ) {
+        let abort = self.restart(path);
+        let total = data.len();
+        self.events = Some(transfer::spawn_paced_send(
+            Cursor::new(data),
+            total,
+            block_size,
+            gap,
+            writer,
+            abort,
+        ));
+    }
+
+    /// Sends `path` in blocks that each wait for an ACK in `rx`.
+    fn start_handshake(
+        &mut self,
+        path: &Path,
+        config: HandshakeConfig,
+        writer: Writer,
+        rx: Receiver<Vec<u8>>,
+    ) {
+        let abort = self.restart(path);
+        match std::fs::read(path) {
+            Ok(data) => {
+                self.events = Some(transfer::spawn_handshake_send(
+                    data, config, writer, rx, abort,
+                ));
+            }
+            Err(e) => self.result = format!("Cannot read {}: {}", path.display(), e),
+        }
+    }
+
+    /// Sends `chunks` from `path` with the timing they were captured at.
+    fn start_timed(&mut self, path: &Path, chunks: Vec<(Duration, Vec<u8>)>, writer: Writer) {
+        let abort = self.restart(path);
+        self.events = Some(transfer::spawn_timed_send(chunks, writer, abort));
+    }
+
+    /// Resets the state for a new send of `path`, returning its abort flag.
+    fn restart(&mut self, path: &Path) -> Arc<AtomicBool> {
+        self.file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.progress = 0.0;
+        self.result.clear();
+        self.abort = Arc::default();
+        Arc::clone(&self.abort)
+    }
+
+    /// Takes in progress reports. Returns `true` while the send is running.
+    fn poll(&mut self, errors: &ErrorCounters) -> bool {
+        let Some(events) = &self.events else {
+            return false;
+        };
+        let mut finished = false;
+        for event in events.try_iter() {
+            match event {
+                TransferEvent::Progress { sent, total } => {
+                    self.progress = sent as f32 / total.max(1) as f32;
+                }
+                TransferEvent::Done { sent } => {
+                    self.progress = 1.0;
+                    self.result = format!("Done, {} bytes sent", sent);
+                    finished = true;
+                }
+                TransferEvent::Failed { sent, error } => {
+                    self.result = format!("Failed after {} bytes: {}", sent, error);
+                    errors.add(ErrorCategory::Write, 1);
+                    finished = true;
+                }
+                TransferEvent::Cancelled { sent } => {
+                    self.result = format!("Cancelled after {} bytes", sent);
+                    finished = true;
+                }
+            }
+        }
+        if finished {
+            self.events = None;
+        }
+        !finished
+    }
+
+    fn ui(&self, ui: &mut egui::Ui) {
+        ui.label(&self.file_name);
+        ui.add(egui::ProgressBar::new(self.progress).show_percentage());
+        if !self.result.is_empty() {
+            ui.label(&self.result);
+        }
+        if self.events.is_some() && ui.button("Cancel").clicked() {
+            self.abort.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+struct SendFile {
+    open: bool,
+    chunk: usize,
+    gap_ms: u64,
+    transfer: PacedSend,
+}
+
+impl Default for SendFile {
+    fn default() -> Self {
+        Self {
+            open: false,
+            chunk: 64,
+            gap_ms: 0,
+            transfer: PacedSend::default(),
+        }
+    }
+}
+
+/// Sends a saved capture back out through the port.
+struct ReplayToPort {
+    open: bool,
+    /// Keep the gaps between chunks of a timestamped capture instead of
+    /// pacing by `chunk` and `gap_ms`.
+    use_timing: bool,
+    /// Which side of a timestamped capture to send. Raw logs only hold
+    /// received data.
+    direction: Direction,
+    chunk: usize,
+    gap_ms: u64,
+    transfer: PacedSend,
+}
+
+impl Default for ReplayToPort {
+    fn default() -> Self {
+        Self {
+            open: false,
+            use_timing: true,
+            direction: Direction::Rx,
+            chunk: 64,
+            gap_ms: 0,
+            transfer: PacedSend::default(),
+        }
+    }
+}
+
+impl ReplayToPort {
+    /// Timestamped captures are sent entry by entry; anything else is taken
+    /// as a raw log and sent as it is. A capture with a broken line isn't
+    /// sent at all.
+    fn start(&mut self, path: &Path, writer: Writer) -> Result<(), String> {
+        let gap = Duration::from_millis(self.gap_ms);
+        match capture::read_capture(path) {
+            Ok(entries) if !entries.is_empty() => {
+                let chunks = capture::timed_chunks(entries, self.direction);
+                if self.use_timing {
+                    self.transfer.start_timed(path, chunks, writer);
+                } else {
+                    let data = chunks.into_iter().flat_map(|(_, d)| d).collect();
+                    self.transfer
+                        .start_data(path, data, self.chunk, gap, writer);
+                }
+            }
+            Err(e) if capture::is_capture(path) => {
+                return Err(format!("Cannot replay {}: {}", path.display(), e));
+            }
+            _ => self.transfer.start(path, self.chunk, gap, writer),
+        }
+        Ok(())
+    }
+}
+
+/// One port with its own settings, reader and received data. Scripts are
+/// attached to the first connection's buffer.
+struct Connection {
+    /// Tells connections apart for transfers, which aren't tied to a tab.
+    id: usize,
+    transport: TransportKind,
+    /// The serial port to open, picked from the list or typed in for ports
+    /// the system doesn't list.
+    port_name: String,
+    /// `host:port` used when `transport` is TCP.
+    tcp_address: String,
     baud_rate: u32,
+    data_bits: DataBits,
     parity: Parity,
     stop_bits: StopBits,
+    flow_control: FlowControl,
     connected: bool,
+    /// Why the last connect attempt failed, shown under the port row.
+    last_error: Option<String>,
+    /// Last level set on the DTR and RTS lines.
+    dtr: bool,
+    rts: bool,
+    reconnect: Option<Reconnect>,
+    rx_buffer: RxBuffer,
+    session: Option<SerialSession>,
+    session_events: Option<Receiver<SessionEvent>>,
+    /// Where received data is logged, if logging is on.
+    log_file: Option<LogFile>,
+    line_endings: LineEndingStats,
+    /// Bytes moved since the last Clear, and the receive rate.
+    traffic: Traffic,
+    rx_rate: RateMeter,
+    /// Both directions, for the Timeline view.
+    timeline: Timeline,
+    /// Labels placed in the received data, in order.
+    markers: Vec<Marker>,
+    /// Snapshot the views show while the display is paused. The reader keeps
+    /// filling `rx_buffer` meanwhile.
+    paused: Option<RxData>,
+    /// Zero point for relative timestamps, reset on every connect.
+    connected_at: DateTime<Local>,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            transport: TransportKind::Serial,
+            port_name: String::new(),
+            tcp_address: String::new(),
+            baud_rate: 115_200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            connected: false,
+            last_error: None,
+            dtr: false,
+            rts: false,
+            reconnect: None,
+            rx_buffer: RxBuffer::new(),
+            session: None,
+            session_events: None,
+            log_file: None,
+            line_endings: LineEndingStats::default(),
+            traffic: Traffic::new(),
+            rx_rate: RateMeter::default(),
+            timeline: Timeline::new(),
+            markers: Vec::new(),
+            paused: None,
+            connected_at: Local::now(),
+        }
+    }
+}
+
+impl Connection {
+    /// Adds a display-only marker at the current end of the received data.
+    /// Markers whose data has been dropped go, and text typed at the same
+    /// place is added to the echo already there.
+    fn push_marker(&mut self, label: String, kind: MarkerKind) {
+        let offset = {
+            let rx = self.rx_buffer.lock();
+            rx.dropped + rx.bytes.len() as u64
+        };
+        self.push_marker_at(offset, label, kind);
+    }
+
+    /// Like [`Self::push_marker`], at stream offset `offset`.
+    fn push_marker_at(&mut self, offset: u64, label: String, kind: MarkerKind) {
+        let dropped = self.rx_buffer.lock().dropped;
+        self.markers.retain(|m| m.offset >= dropped);
+        if kind == MarkerKind::LocalEcho
+            && let Some(last) = self.markers.last_mut()
+            && last.kind == kind
+            && last.offset == offset
+        {
+            last.label += &label;
+            return;
+        }
+        self.markers.push(Marker {
+            offset,
+            label,
+            kind,
+        });
+    }
+
+    /// Empties the received data and what is kept alongside it: the paused
+    /// copy, line ending counts, byte counters, the timeline and markers.
+    fn clear_rx(&mut self) {
+        self.rx_buffer.clear();
+        if let Some(frozen) = &mut self.paused {
+            *frozen = RxData::default();
+        }
+        self.line_endings = LineEndingStats::default();
+        self.traffic.reset();
+        self.timeline.clear();
+        self.markers.clear();
+    }
+
+    /// What the connection's tab is called.
+    fn label(&self) -> String {
+        match (&self.session, &self.reconnect) {
+            (Some(session), _) => session.settings().port_name,
+            (None, Some(reconnect)) => format!("{} (reconnecting)", reconnect.settings.port_name),
+            (None, None) => "Not connected".to_string(),
+        }
+    }
+
+    /// Keeps a connection that isn't shown up to date: the line ending stats
+    /// keep counting, line errors are marked and a port that closes is let go.
+    fn poll_background(&mut self) {
+        let Some(events) = &self.session_events else {
+            return;
+        };
+        let mut closed = false;
+        let events: Vec<SessionEvent> = events.try_iter().collect();
+        for event in events {
+            match event {
+                SessionEvent::Data(bytes) => self.line_endings.feed(&bytes),
+                SessionEvent::Closed(_) => closed = true,
+                SessionEvent::WriteError { device_gone, .. } => closed |= device_gone,
+                SessionEvent::LineError { marker, offset, .. } => {
+                    self.push_marker_at(offset, marker.to_string(), MarkerKind::LineError);
+                }
+                SessionEvent::Sent(_) => {}
+            }
+        }
+        if closed {
+            self.connected = false;
+            self.session = None;
+            self.session_events = None;
+            self.log_file = None;
+        }
+    }
+}
+
+struct UartApp {
+    mode: Mode,
+    /// The connection shown; its slot in `connections` holds a placeholder.
+    conn: Connection,
+    connections: Vec<Connection>,
+    active: usize,
+    ports: Vec<SerialPortInfo>,
+    /// Why the ports could not be listed on the last refresh.
+    ports_error: Option<String>,
+    auto_reconnect: bool,
+    clear_on_connect: bool,
+    /// How long "Send Break" holds the line.
+    break_ms: u64,
     tx_buffer: String,
-    rx_buffer: Arc<Mutex<String>>,
-    port_handle: Option<Arc<Mutex<Box<dyn SerialPort>>>>,
+    /// Send `tx_buffer` as hex bytes instead of text.
+    tx_hex: bool,
+    /// Appended to whatever the Debug Send button sends.
+    tx_line_ending: Option<LineEnding>,
+    /// Pause after each sent byte, for devices that drop characters.
+    send_delay_ms: u64,
+    /// Previously sent Debug inputs, oldest first.
+    tx_history: Vec<String>,
+    /// Entry of `tx_history` currently recalled into `tx_buffer`.
+    history_index: Option<usize>,
+    status: String,
+    errors: ErrorCounters,
+    show_errors: bool,
+    /// Format of the next log file.
+    log_format: LogFormat,
+    /// Label for the next marker.
+    marker_label: String,
+    /// Recent log lines, shown in a panel above the status bar.
+    recent_log: RecentLog,
+    show_log: bool,
+    /// How script runs went, shown in their own panel.
+    script_report_chan: Option<Receiver<ScriptEvent>>,
+    script_output: Vec<ScriptOutput>,
+    show_script_output: bool,
+    echo_tx: bool,
+    /// Show what is typed in Terminal mode without waiting for the device to
+    /// echo it.
+    local_echo: bool,
+    highlights: Vec<Highlight>,
+    new_highlight: String,
+    macros: Vec<Macro>,
+    /// The macro editor is shown.
+    edit_macros: bool,
+    /// Rules run on received data, the copy the matching thread uses and
+    /// what they did lately.
+    triggers: Vec<Trigger>,
+    trigger_table: TriggerTable,
+    trigger_chan: Option<Receiver<Fired>>,
+    trigger_log: Vec<String>,
+    edit_triggers: bool,
+    selection: String,
+    replay: Option<ReplayState>,
+    autobaud: AutobaudState,
+    counter: CounterTest,
+    handshake: HandshakeSend,
+    program: PacedSend,
+    send_file: SendFile,
+    replay_to_port: ReplayToPort,
+    title: String,
+    flush_interval_ms: u64,
+    read_timeout_ms: u64,
+    poll_interval_ms: u64,
+    read_chunk: usize,
+    /// Most received bytes kept for display.
+    rx_max_bytes: usize,
+    non_printable: NonPrintable,
+    /// Wrap long lines in the ASCII view instead of scrolling sideways.
+    wrap_ascii: bool,
+    /// How the ASCII and Terminal views decode received bytes.
+    encoding: Encoding,
+    /// How the Terminal view treats a lone carriage return.
+    carriage_return: CarriageReturn,
+    /// Keep the Debug views at the newest data.
+    auto_scroll: bool,
+    /// Which Debug views are shown, and the share of the width the ASCII view
+    /// gets when both are.
+    show_ascii: bool,
+    show_hex: bool,
+    split: f32,
+    search: String,
+    search_ignore_case: bool,
+    /// Index of the selected hit and how many there were on the last frame.
+    search_hit: usize,
+    search_matches: usize,
+    /// Scroll the ASCII view to the selected hit on the next frame.
+    scroll_to_hit: bool,
+    /// Asking before a large receive buffer is cleared.
+    confirm_clear: bool,
+    /// Set for one frame after unpausing so the views scroll to the end.
+    jump_to_tail: bool,
+    /// Bytes per row in the hex view.
+    hex_columns: usize,
+    /// Size of the monospace text in the receive views.
+    font_size: f32,
+    dark_mode: bool,
+    timestamps: bool,
+    timestamp_mode: TimestampMode,
+    /// Where the script and firmware file dialogs open next.
+    script_dir: Option<PathBuf>,
+    firmware_dir: Option<PathBuf>,
     windows: Vec<Window>,
+    /// Where each script window was last seen, by name.
+    window_rects: HashMap<String, egui::Rect>,
     window_chan: Option<Receiver<WndOp>>,
+    port_chan: Option<Receiver<PortRequest>>,
+    send_chan: Option<Receiver<Vec<u8>>>,
+    /// Scripts asking for the connection status.
+    status_chan: Option<Receiver<Sender<PortStatus>>>,
     script_ch: Option<Sender<PathBuf>>,
+    /// Seconds between runs of a loaded script, 0 to run it once.
+    script_every_s: u32,
+    script_repeat: Option<ScriptRepeat>,
     //rhai_engine: Engine,
 }
 
@@ -53,150 +836,557 @@ impl Default for UartApp {
     fn default() -> Self {
         Self {
             mode: Mode::Debug,
-            ports: serialport::available_ports().unwrap_or_default(),
-            selected_port: None,
-            baud_rate: 115_200,
-            parity: Parity::None,
-            stop_bits: StopBits::One,
-            connected: false,
+            conn: Connection::default(),
+            connections: vec![Connection::default()],
+            active: 0,
+            ports: Vec::new(),
+            ports_error: None,
+            auto_reconnect: false,
+            clear_on_connect: false,
+            break_ms: 250,
             tx_buffer: String::new(),
-            rx_buffer: Arc::new(Mutex::new(String::new())),
-            port_handle: None,
+            tx_hex: false,
+            tx_line_ending: None,
+            send_delay_ms: 0,
+            tx_history: Vec::new(),
+            history_index: None,
+            status: String::new(),
+            errors: ErrorCounters::new(),
+            show_errors: false,
+            log_format: LogFormat::Raw,
+            marker_label: String::new(),
+            recent_log: RecentLog::default(),
+            show_log: false,
+            script_report_chan: None,
+            script_output: Vec::new(),
+            show_script_output: false,
+            echo_tx: false,
+            local_echo: false,
             script_ch: None,
+            script_every_s: 0,
+            script_repeat: None,
+            highlights: Vec::new(),
+            macros: Vec::new(),
+            edit_macros: false,
+            triggers: Vec::new(),
+            trigger_table: TriggerTable::default(),
+            trigger_chan: None,
+            trigger_log: Vec::new(),
+            edit_triggers: false,
+            new_highlight: String::new(),
+            selection: String::new(),
+            replay: None,
+            autobaud: AutobaudState::default(),
+            counter: CounterTest::default(),
+            handshake: HandshakeSend::default(),
+            program: PacedSend::default(),
+            send_file: SendFile::default(),
+            replay_to_port: ReplayToPort::default(),
+            title: APP_TITLE.to_string(),
+            flush_interval_ms: 10,
+            read_timeout_ms: 100,
+            poll_interval_ms: 10,
+            read_chunk: DEFAULT_READ_CHUNK,
+            rx_max_bytes: DEFAULT_MAX_BYTES,
+            non_printable: NonPrintable::Hide,
+            wrap_ascii: true,
+            encoding: Encoding::Utf8,
+            carriage_return: CarriageReturn::Overwrite,
+            auto_scroll: true,
+            show_ascii: true,
+            show_hex: true,
+            split: 0.5,
+            search: String::new(),
+            search_ignore_case: true,
+            search_hit: 0,
+            search_matches: 0,
+            scroll_to_hit: false,
+            confirm_clear: false,
+            jump_to_tail: false,
+            hex_columns: 8,
+            font_size: DEFAULT_FONT_SIZE,
+            dark_mode: true,
+            timestamps: false,
+            timestamp_mode: TimestampMode::WallClock,
+            script_dir: None,
+            firmware_dir: None,
             windows: Vec::new(),
+            window_rects: HashMap::new(),
             window_chan: None,
+            port_chan: None,
+            send_chan: None,
+            status_chan: None,
             //rhai_engine: Engine::new(),
         }
     }
 }
 //Todo scripting rhai, midi script,
 impl App for UartApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, CONFIG_KEY, &self.config());
+    }
+
+    /// Lets every open port finish sending and closes its log before the
+    /// process ends.
+    fn on_exit(&mut self, _: Option<&eframe::glow::Context>) {
+        for conn in std::iter::once(&mut self.conn).chain(&mut self.connections) {
+            let Some(session) = conn.session.take() else {
+                continue;
+            };
+            let port_name = session.settings().port_name;
+            if let Err(e) = session.close(EXIT_FLUSH_TIMEOUT) {
+                log::warn!("Closing {} on exit: {}", port_name, e);
+            }
+        }
+        log::logger().flush();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        if ctx.input_mut(|i| i.consume_shortcut(&CONNECT_SHORTCUT)) {
+            self.toggle_connection();
+        }
         if let Some(ch) = &self.window_chan {
-            let rslt = ch.try_recv();
-            match rslt {
-                Ok(WndOp::New(id, name)) => {
-                    let wnd = Window {
-                        id: id,
-                        name: name,
-                        text: String::from("hello"),
-                    };
-                    self.windows.push(wnd);
-                    println!("new window");
+            let mut received = false;
+            let mut clear_requested = false;
+            for op in ch.try_iter() {
+                received = true;
+                match op {
+                    WndOp::New(id, name) => {
+                        let wnd = Window {
+                            id,
+                            name,
+                            text: String::from("hello"),
+                            monospace: true,
+                            max_len: WINDOW_TEXT_LIMIT,
+                        };
+                        self.windows.push(wnd);
+                        log::debug!("script opened window {}", id);
+                    }
+                    WndOp::WriteText(id, text) => {
+                        if let Some(found) = self.windows.iter_mut().find(|wnd| wnd.id == id) {
+                            found.append(&text);
+                        };
+                    }
+                    WndOp::SetFont(id, monospace) => {
+                        if let Some(found) = self.windows.iter_mut().find(|wnd| wnd.id == id) {
+                            found.monospace = monospace;
+                        };
+                    }
+                    WndOp::SetLimit(id, max_len) => {
+                        if let Some(found) = self.windows.iter_mut().find(|wnd| wnd.id == id) {
+                            found.max_len = max_len;
+                            found.trim();
+                        };
+                    }
+                    WndOp::Close(id) => self.windows.retain(|wnd| wnd.id != id),
+                    WndOp::ClearRx => clear_requested = true,
                 }
-                Ok(WndOp::WriteText(id, text)) => {
-                    if let Some(found) = self.windows.iter_mut().find(|wnd| wnd.id == id) {
-                        found.text += &text;
-                    };
+            }
+            // Scripts read the first connection, so that is the one cleared.
+            if clear_requested {
+                if self.active == 0 {
+                    self.clear_rx();
+                } else {
+                    self.connections[0].clear_rx();
+                }
+            }
+            // Script output shows up right away instead of on the next idle tick.
+            if received {
+                ctx.request_repaint();
+            }
+        }
+
+        self.poll_script_reports();
+
+        if let Some(ch) = &self.trigger_chan {
+            for fired in ch.try_iter() {
+                let pattern = self
+                    .triggers
+                    .get(fired.rule)
+                    .map_or("?", |t| t.pattern.as_str());
+                let entry = match fired.result {
+                    Ok(()) => format!("'{}' matched: {}", pattern, fired.line),
+                    Err(e) => {
+                        log::warn!("Trigger '{}' failed: {}", pattern, e);
+                        self.status = format!("Trigger '{}' failed: {}", pattern, e);
+                        format!("'{}' failed: {}", pattern, e)
+                    }
+                };
+                self.trigger_log.push(entry);
+            }
+            let excess = self.trigger_log.len().saturating_sub(TRIGGER_LOG_LINES);
+            self.trigger_log.drain(..excess);
+        }
+
+        // Scripts and triggers read the first connection, so they write to
+        // it too, whichever tab is shown.
+        let script_conn = if self.active == 0 {
+            &mut self.conn
+        } else {
+            &mut self.connections[0]
+        };
+
+        if let Some(ch) = &self.status_chan {
+            for reply in ch.try_iter() {
+                let _ = reply.send(PortStatus::of(script_conn.session.as_ref()));
+            }
+        }
+
+        // Sends go first so a flush covers what the script sent before it.
+        if let Some(ch) = &self.send_chan {
+            for data in ch.try_iter() {
+                match &script_conn.session {
+                    Some(session) => session.write(&data),
+                    None => self.status = "Script send ignored: not connected".to_string(),
+                }
+            }
+        }
+
+        if let Some(ch) = &self.port_chan {
+            for request in ch.try_iter() {
+                // Flushing waits for the writer, which must not stall the UI.
+                if let (PortOp::Flush, Some(session)) = (&request.op, &script_conn.session) {
+                    // Sends that arrived since the loop above are queued first.
+                    if let Some(sends) = &self.send_chan {
+                        for data in sends.try_iter() {
+                            session.write(&data);
+                        }
+                    }
+                    session.writer().flush_then(move |result| {
+                        request.finish(result.map_err(|e| e.to_string()))
+                    });
+                    continue;
+                }
+                let result = match &script_conn.session {
+                    Some(session) => session.apply(request.op.clone()).map_err(|e| e.to_string()),
+                    None => Err("not connected".to_string()),
+                };
+                match &result {
+                    Ok(()) => match request.op {
+                        PortOp::SetBaud(rate) => script_conn.baud_rate = rate,
+                        PortOp::SetDtr(level) => script_conn.dtr = level,
+                        PortOp::SetRts(level) => script_conn.rts = level,
+                        PortOp::Break(_) | PortOp::Flush => {}
+                    },
+                    Err(e) => self.status = format!("Script port operation failed: {}", e),
                 }
-                _ => (),
+                request.finish(result);
             }
         }
 
+        self.poll_session_events();
+        for connection in &mut self.connections {
+            connection.poll_background();
+        }
+        self.tick_reconnect(ctx);
+        self.tick_script_repeat(ctx);
+        self.tick_replay(ctx);
+        self.tick_counter(ctx);
+        self.update_title(ctx);
+        self.apply_font_size(ctx);
+        let theme = if self.dark_mode {
+            egui::Theme::Dark
+        } else {
+            egui::Theme::Light
+        };
+        if ctx.theme() != theme {
+            ctx.set_theme(theme);
+        }
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(&self.status);
+                ui.separator();
+                ui.label(format!(
+                    "Line endings: {}",
+                    self.conn.line_endings.summary()
+                ));
+                ui.separator();
+                let rate = self.conn.rx_rate.sample(self.conn.traffic.rx());
+                ui.label(format!(
+                    "RX: {} B ({:.0} B/s)  TX: {} B",
+                    self.conn.traffic.rx(),
+                    rate,
+                    self.conn.traffic.tx()
+                ));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let total = self.errors.total();
+                    let text = egui::RichText::new(format!("Errors: {}", total));
+                    let text = if total > 0 {
+                        text.color(Color32::RED)
+                    } else {
+                        text
+                    };
+                    if ui.selectable_label(self.show_errors, text).clicked() {
+                        self.show_errors = !self.show_errors;
+                    }
+                    ui.toggle_value(&mut self.show_log, "Log")
+                        .on_hover_text("Show recent diagnostics, also written to the log file");
+                    ui.toggle_value(&mut self.show_script_output, "Scripts")
+                        .on_hover_text("Show how script runs went and where they failed");
+                });
+            });
+        });
+
+        egui::TopBottomPanel::bottom("script_output_panel")
+            .resizable(true)
+            .show_animated(ctx, self.show_script_output, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Script output");
+                    if ui.small_button("Clear").clicked() {
+                        self.script_output.clear();
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        for entry in &self.script_output {
+                            let text = egui::RichText::new(&entry.text).monospace();
+                            if entry.failed {
+                                ui.label(text.color(ui.visuals().error_fg_color));
+                            } else {
+                                ui.label(text);
+                            }
+                        }
+                    });
+            });
+
+        egui::TopBottomPanel::bottom("log_panel")
+            .resizable(true)
+            .show_animated(ctx, self.show_log, |ui| {
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        for line in self.recent_log.lines() {
+                            ui.monospace(line);
+                        }
+                    });
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            // First row with selection buttons (Port, Baud rate, Parity, Stop Bits)
+            ui.horizontal(|ui| self.connection_tabs(ui));
+            ui.separator();
+            // First row with selection buttons (Port, Baud rate, Data Bits, Parity, Stop Bits, Flow)
             ui.horizontal(|ui| {
-                ui.label("Port:");
-                let port_names: Vec<String> =
-                    self.ports.iter().map(|p| p.port_name.clone()).collect();
-                ComboBox::from_id_salt("port_select")
-                    .selected_text(
-                        self.selected_port
-                            .and_then(|i| port_names.get(i))
-                            .unwrap_or(&"<Select>".to_string())
-                            .clone(),
-                    )
+                ComboBox::from_id_salt("transport_select")
+                    .selected_text(match self.conn.transport {
+                        TransportKind::Serial => "Serial",
+                        TransportKind::Tcp => "TCP",
+                    })
                     .show_ui(ui, |ui| {
-                        for (i, name) in port_names.iter().enumerate() {
-                            ui.selectable_value(&mut self.selected_port, Some(i), name);
-                        }
+                        ui.selectable_value(
+                            &mut self.conn.transport,
+                            TransportKind::Serial,
+                            "Serial",
+                        );
+                        ui.selectable_value(&mut self.conn.transport, TransportKind::Tcp, "TCP");
                     });
+                if self.conn.transport == TransportKind::Tcp {
+                    ui.label("Address:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.conn.tcp_address)
+                            .hint_text("host:port")
+                            .desired_width(160.0),
+                    )
+                    .on_hover_text("Raw TCP socket, e.g. a serial server or ser2net");
+                } else {
+                    ui.label("Port:");
+                    ComboBox::from_id_salt("port_select")
+                        .selected_text(if self.conn.port_name.is_empty() {
+                            "<Select>"
+                        } else {
+                            &self.conn.port_name
+                        })
+                        .show_ui(ui, |ui| {
+                            for port in &self.ports {
+                                ui.selectable_value(
+                                    &mut self.conn.port_name,
+                                    port.port_name.clone(),
+                                    &port.port_name,
+                                );
+                            }
+                        });
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.conn.port_name)
+                            .hint_text("or type a name")
+                            .desired_width(110.0),
+                    )
+                    .on_hover_text("Any device path, e.g. /dev/ttyUSB0 or COM17, listed or not");
+                    if ui
+                        .button("⟳")
+                        .on_hover_text("Refresh available ports")
+                        .clicked()
+                    {
+                        self.refresh_ports();
+                    }
+                    if let Some(error) = &self.ports_error {
+                        ui.colored_label(Color32::RED, error);
+                    } else if self.ports.is_empty() {
+                        let color = ui.visuals().warn_fg_color;
+                        ui.colored_label(
+                            color,
+                            "No serial ports detected — plug in a device and click ⟳, or type its name",
+                        );
+                    }
+                }
 
                 ui.label("Baud:");
                 ui.add(
-                    egui::DragValue::new(&mut self.baud_rate)
+                    egui::DragValue::new(&mut self.conn.baud_rate)
                         .speed(100)
-                        .range(1_200..=921_600),
-                );
+                        .range(1..=u32::MAX),
+                )
+                .on_hover_text("Drag or double-click to type any rate");
+                ComboBox::from_id_salt("baud_preset")
+                    .selected_text("Presets")
+                    .width(70.0)
+                    .show_ui(ui, |ui| {
+                        for rate in autobaud::COMMON_BAUD_RATES {
+                            ui.selectable_value(&mut self.conn.baud_rate, rate, rate.to_string());
+                        }
+                    });
+
+                ui.label("Data Bits:");
+                ComboBox::from_id_salt("databits_select")
+                    .selected_text(u8::from(self.conn.data_bits).to_string())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.conn.data_bits, DataBits::Five, "5");
+                        ui.selectable_value(&mut self.conn.data_bits, DataBits::Six, "6");
+                        ui.selectable_value(&mut self.conn.data_bits, DataBits::Seven, "7");
+                        ui.selectable_value(&mut self.conn.data_bits, DataBits::Eight, "8");
+                    });
 
                 ui.label("Parity:");
                 ComboBox::from_id_salt("parity_select")
-                    .selected_text(format!("{:?}", self.parity))
+                    .selected_text(format!("{:?}", self.conn.parity))
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.parity, Parity::None, "None");
-                        ui.selectable_value(&mut self.parity, Parity::Even, "Even");
-                        ui.selectable_value(&mut self.parity, Parity::Odd, "Odd");
+                        ui.selectable_value(&mut self.conn.parity, Parity::None, "None");
+                        ui.selectable_value(&mut self.conn.parity, Parity::Even, "Even");
+                        ui.selectable_value(&mut self.conn.parity, Parity::Odd, "Odd");
                     });
 
                 ui.label("Stop Bits:");
                 ComboBox::from_id_salt("stopbit_select")
-                    .selected_text(format!("{:?}", self.stop_bits))
+                    .selected_text(format!("{:?}", self.conn.stop_bits))
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.stop_bits, StopBits::One, "1");
-                        ui.selectable_value(&mut self.stop_bits, StopBits::Two, "2");
+                        ui.selectable_value(&mut self.conn.stop_bits, StopBits::One, "1");
+                        ui.selectable_value(&mut self.conn.stop_bits, StopBits::Two, "2");
                     });
 
-                if !self.connected {
-                    if ui.button("Connect").clicked() {
-                        if let Some(index) = self.selected_port {
-                            let port_name = &self.ports[index].port_name;
-                            match serialport::new(port_name, self.baud_rate)
-                                .parity(self.parity)
-                                .stop_bits(self.stop_bits)
-                                .timeout(Duration::from_millis(100))
-                                .open()
-                            {
-                                Ok(p) => {
-                                    let arc_port = Arc::new(Mutex::new(p));
-                                    let rx_buffer = Arc::clone(&self.rx_buffer);
-                                    let port_clone = Arc::clone(&arc_port);
-                                    thread::spawn(move || {
-                                        let mut buf = [0u8; 128];
-                                        loop {
-                                            let mut port = port_clone.lock().unwrap();
-                                            match port.read(&mut buf) {
-                                                Ok(n) if n > 0 => {
-                                                    let mut out = rx_buffer.lock().unwrap();
-                                                    out.push_str(&String::from_utf8_lossy(
-                                                        &buf[..n],
-                                                    ));
-                                                }
-                                                Ok(_) => {
-                                                    // No data, avoid hogging CPU
-                                                    drop(port);
-                                                    thread::sleep(Duration::from_millis(10));
-                                                }
-                                                Err(ref e)
-                                                    if e.kind() == std::io::ErrorKind::TimedOut =>
-                                                {
-                                                    // Timeout — expected
-                                                    drop(port);
-                                                    thread::sleep(Duration::from_millis(10));
-                                                }
-                                                Err(_) => {
-                                                    // Other errors — optional: break or log
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    });
-                                    self.port_handle = Some(arc_port);
-                                    self.connected = true;
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to open port: {}", e);
-                                }
-                            }
-                        }
+                ui.label("Flow:");
+                ComboBox::from_id_salt("flow_select")
+                    .selected_text(format!("{:?}", self.conn.flow_control))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.conn.flow_control, FlowControl::None, "None");
+                        ui.selectable_value(
+                            &mut self.conn.flow_control,
+                            FlowControl::Software,
+                            "Software (XON/XOFF)",
+                        );
+                        ui.selectable_value(
+                            &mut self.conn.flow_control,
+                            FlowControl::Hardware,
+                            "Hardware (RTS/CTS)",
+                        );
+                    });
+
+                ui.label("Flush:");
+                ui.add(
+                    egui::DragValue::new(&mut self.flush_interval_ms)
+                        .range(0..=1000)
+                        .suffix(" ms"),
+                )
+                .on_hover_text(
+                    "How long received data is batched before display (applies on connect)",
+                );
+
+                ui.label("Timeout:");
+                ui.add(
+                    egui::DragValue::new(&mut self.read_timeout_ms)
+                        .range(1..=10_000)
+                        .suffix(" ms"),
+                )
+                .on_hover_text("How long each read waits for data (applies on connect)");
+                ui.label("Poll:");
+                ui.add(
+                    egui::DragValue::new(&mut self.poll_interval_ms)
+                        .range(1..=1000)
+                        .suffix(" ms"),
+                )
+                .on_hover_text(
+                    "Pause between reads when the line is idle, also the display refresh \
+                     interval while connected (applies on connect)",
+                );
+                ui.label("Chunk:");
+                ui.add(
+                    egui::DragValue::new(&mut self.read_chunk)
+                        .range(MIN_READ_CHUNK..=MAX_READ_CHUNK)
+                        .speed(16)
+                        .suffix(" B"),
+                )
+                .on_hover_text(
+                    "Most bytes taken per read: smaller for lower latency, larger for fast \
+                     lines (applies on connect)",
+                );
+
+                ui.label("Keep:");
+                let mut kib = self.rx_max_bytes / 1024;
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut kib)
+                            .range(4..=262_144)
+                            .speed(16)
+                            .suffix(" KiB"),
+                    )
+                    .on_hover_text("Older received data is dropped beyond this size")
+                    .changed()
+                {
+                    self.rx_max_bytes = kib * 1024;
+                    for connection in std::iter::once(&self.conn).chain(&self.connections) {
+                        connection.rx_buffer.set_max_bytes(self.rx_max_bytes);
                     }
-                } else {
-                    if ui.button("Disconnect").clicked() {
-                        self.connected = false;
-                        self.port_handle = None;
-                        
+                }
+
+                if let Some(reconnect) = &self.conn.reconnect {
+                    ui.spinner();
+                    ui.label(format!(
+                        "Reconnecting to {} (attempt {})",
+                        reconnect.settings.port_name,
+                        reconnect.attempts + 1
+                    ));
+                    if ui.button("Cancel").clicked() {
+                        self.disconnect("Reconnect cancelled");
                     }
+                } else if !self.conn.connected {
+                    if ui
+                        .add_enabled(self.port_settings().is_some(), egui::Button::new("Connect"))
+                        .on_hover_text(CONNECT_SHORTCUT_HINT)
+                        .on_disabled_hover_text("Select a port or enter an address first")
+                        .clicked()
+                    {
+                        self.toggle_connection();
+                    }
+                } else if ui
+                    .button("Disconnect")
+                    .on_hover_text(CONNECT_SHORTCUT_HINT)
+                    .clicked()
+                {
+                    self.toggle_connection();
                 }
+                ui.checkbox(&mut self.auto_reconnect, "Auto-reconnect")
+                    .on_hover_text("Keep trying to reopen the port if it goes away");
+                ui.checkbox(&mut self.clear_on_connect, "Clear on connect")
+                    .on_hover_text(
+                        "Start with an empty display when connecting instead of adding to \
+                         what is there. Automatic reconnects always keep it.",
+                    );
             });
+            if let Some(error) = &self.conn.last_error {
+                ui.colored_label(Color32::RED, error);
+            }
+            ui.horizontal(|ui| self.line_control_ui(ui));
             ui.separator();
             ui.horizontal(|ui| {
                 ui.label("Mode:");
@@ -205,136 +1395,383 @@ impl App for UartApp {
                     .show_ui(ui, |ui| {
                         ui.selectable_value(&mut self.mode, Mode::Terminal, "Terminal");
                         ui.selectable_value(&mut self.mode, Mode::Debug, "Debug");
+                        ui.selectable_value(&mut self.mode, Mode::Timeline, "Timeline");
                     });
+                ui.label("Font:");
+                ui.add(
+                    egui::DragValue::new(&mut self.font_size)
+                        .range(6.0..=40.0)
+                        .speed(0.25)
+                        .suffix(" pt"),
+                )
+                .on_hover_text("Text size of the receive views");
+                ComboBox::from_id_salt("encoding")
+                    .selected_text(self.encoding.label())
+                    .show_ui(ui, |ui| {
+                        for encoding in Encoding::ALL {
+                            ui.selectable_value(&mut self.encoding, encoding, encoding.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("How received bytes are decoded; the hex view is unaffected");
+                ui.label("Char delay:");
+                ui.add(
+                    egui::DragValue::new(&mut self.send_delay_ms)
+                        .range(0..=1000)
+                        .suffix(" ms"),
+                )
+                .on_hover_text(
+                    "Pause after every byte sent from Debug or Terminal; 0 sends at full speed",
+                );
+                let theme_icon = if self.dark_mode { "☀" } else { "🌙" };
+                if ui
+                    .button(theme_icon)
+                    .on_hover_text("Switch between dark and light theme")
+                    .clicked()
+                {
+                    self.dark_mode = !self.dark_mode;
+                }
                 ui.label("Operations");
                 let _ = ComboBox::from_id_salt("op_sel").selected_text("ops");
 
-                if ui.button("load script").clicked() {
-                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                        println!("Selected file: {:?}", path);
-                        // You can now use `path` (a `std::path::PathBuf`)
-                        //let script= read_to_string(path).unwrap();
-                        if let Some(ch) = &self.script_ch {
-                            let _ = ch.send(path);
-                        }
+                if ui.button("load script").clicked()
+                    && let Some(path) = pick_file(&mut self.script_dir)
+                    && let Some(ch) = &self.script_ch
+                {
+                    log::info!("Running script {}", path.display());
+                    let _ = ch.send(path.clone());
+                    self.script_repeat = (self.script_every_s > 0).then(|| ScriptRepeat {
+                        path,
+                        next_run: Instant::now() + Duration::from_secs(self.script_every_s.into()),
+                    });
+                }
+                ui.label("Run every:");
+                ui.add(
+                    egui::DragValue::new(&mut self.script_every_s)
+                        .range(0..=86_400)
+                        .suffix(" s"),
+                )
+                .on_hover_text("Re-run the loaded script on this interval; 0 runs it once");
+                if let Some(repeat) = &self.script_repeat {
+                    let name = repeat.path.file_name().unwrap_or_default();
+                    if ui
+                        .button("Stop repeating")
+                        .on_hover_text(format!("{} is re-run periodically", name.display()))
+                        .clicked()
+                    {
+                        self.script_repeat = None;
                     }
                 }
-                if ui.button("program device").clicked() {
-                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                        if let Ok(file) = File::open(&path) {
-                            let mut reader = BufReader::new(file);
-                            let mut buffer = [0u8; 512];
-                
-                            loop {
-                                match reader.read_exact(&mut buffer) {
-                                    Ok(()) => {
-                                        self.send_to_uart(&buffer);
-                                        thread::sleep(Duration::from_millis(10)); // Wait between blocks
-                                    }
-                                    Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                                        // Partial final block is ignored; optional: pad & send
-                                        break;
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Error reading UF2 file: {}", e);
-                                        break;
-                                    }
+                if self.conn.log_file.is_none() {
+                    ComboBox::from_id_salt("log_format")
+                        .selected_text(match self.log_format {
+                            LogFormat::Raw => "Raw",
+                            LogFormat::Timestamped => "Timestamped",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.log_format, LogFormat::Raw, "Raw")
+                                .on_hover_text("Received bytes only, as they came");
+                            ui.selectable_value(
+                                &mut self.log_format,
+                                LogFormat::Timestamped,
+                                "Timestamped",
+                            )
+                            .on_hover_text(
+                                "A line per chunk with time, direction and hex bytes; \
+                                 can be replayed",
+                            );
+                        });
+                    if ui.button("Log to file...").clicked()
+                        && let Some(path) = rfd::FileDialog::new().save_file()
+                    {
+                        match File::create(&path) {
+                            Ok(file) => {
+                                let file = LogFile::new(file, self.log_format);
+                                if let Some(session) = &self.conn.session {
+                                    session.set_log(Some(file.clone()));
                                 }
+                                self.conn.log_file = Some(file);
+                                self.status = format!("Logging to {}", path.display());
                             }
-                        } else {
-                            eprintln!("Failed to open UF2 file.");
+                            Err(e) => self.status = format!("Cannot create log file: {}", e),
                         }
                     }
+                } else if ui.button("Stop logging").clicked() {
+                    self.stop_logging();
+                    self.status = "Logging stopped".to_string();
+                }
+                if ui.button("handshake send").clicked() {
+                    self.handshake.open = true;
+                }
+                if ui.button("counter test").clicked() {
+                    self.counter.open = true;
+                }
+                if ui.button("autobaud").clicked() {
+                    self.autobaud.open = true;
+                }
+                // Replaying replaces the received data, so it stays out of live sessions.
+                if ui
+                    .add_enabled(!self.conn.connected, egui::Button::new("replay capture"))
+                    .on_disabled_hover_text("Disconnect first")
+                    .clicked()
+                    && let Some(path) = rfd::FileDialog::new().pick_file()
+                {
+                    match capture::read_capture(&path) {
+                        Ok(entries) => {
+                            self.conn.rx_buffer.clear();
+                            self.replay = Some(ReplayState {
+                                replay: Replay::new(entries),
+                                playing: true,
+                                speed: 1.0,
+                                last_tick: Instant::now(),
+                            });
+                        }
+                        Err(e) => self.status = format!("Failed to load capture: {}", e),
+                    }
+                }
+                if ui.button("send file").clicked() {
+                    self.send_file.open = true;
+                }
+                if ui.button("replay to port").clicked() {
+                    self.replay_to_port.open = true;
+                }
+                let programming = self.program.events.is_some();
+                if ui
+                    .add_enabled(
+                        self.conn.connected && !programming,
+                        egui::Button::new("program device"),
+                    )
+                    .clicked()
+                    && let Some(path) = pick_file(&mut self.firmware_dir)
+                {
+                    self.start_programming(&path);
                 }
             });
+            if self.replay.is_some() {
+                self.replay_ui(ui);
+            }
             match self.mode {
                 Mode::Debug => {
-                    // Send section (Send field and Send button)
-                    ui.horizontal(|ui| {
-                        ui.text_edit_singleline(&mut self.tx_buffer);
-                        if ui.button("Send").clicked() {
-                            self.send_to_uart(self.tx_buffer.as_bytes());
-                        }
-                    });
+                    self.send_row(ui);
+                    ui.horizontal_wrapped(|ui| self.macro_buttons(ui));
                     ui.separator();
                     ui.vertical(|ui| {
                         // Clear button (Placed at the bottom, minimal space)
-                        if ui.button("Clear").clicked() {
-                            let mut rx = self.rx_buffer.lock().unwrap();
-                            rx.clear();
-                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Clear").clicked() {
+                                if self.conn.rx_buffer.lock().bytes.len() > CLEAR_CONFIRM_BYTES {
+                                    self.confirm_clear = true;
+                                } else {
+                                    self.clear_rx();
+                                }
+                            }
+                            if ui.button("Save capture...").clicked() {
+                                self.save_capture();
+                            }
+                            if ui
+                                .button("Export hexdump...")
+                                .on_hover_text("Save in the 16-byte layout of xxd")
+                                .clicked()
+                            {
+                                self.export_hexdump();
+                            }
+                            self.pause_toggle(ui);
+                            ui.separator();
+                            let label = ui.add(
+                                egui::TextEdit::singleline(&mut self.marker_label)
+                                    .hint_text("marker label")
+                                    .desired_width(120.0),
+                            );
+                            let entered =
+                                label.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                            if ui
+                                .button("Insert marker")
+                                .on_hover_text("Mark this point in the received data")
+                                .clicked()
+                                || entered
+                            {
+                                self.insert_marker();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Non-printable:");
+                            ComboBox::from_id_salt("non_printable")
+                                .selected_text(format!("{:?}", self.non_printable))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut self.non_printable,
+                                        NonPrintable::Hide,
+                                        "Hide",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.non_printable,
+                                        NonPrintable::Dot,
+                                        "Dot",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.non_printable,
+                                        NonPrintable::Escape,
+                                        "Escape",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.non_printable,
+                                        NonPrintable::CStyle,
+                                        "C escapes (\\r, \\n, \\t, ...)",
+                                    );
+                                });
+                            ui.checkbox(&mut self.wrap_ascii, "Wrap")
+                                .on_hover_text("Wrap long lines instead of scrolling sideways");
+                            ui.checkbox(&mut self.auto_scroll, "Auto-scroll")
+                                .on_hover_text("Follow new data; turn off to look at older data");
+                            ui.separator();
+                            ui.toggle_value(&mut self.show_ascii, "ASCII");
+                            ui.toggle_value(&mut self.show_hex, "Hex");
+                        });
+                        ui.horizontal(|ui| self.search_ui(ui));
+                        ui.collapsing("Highlights", |ui| self.highlights_ui(ui));
                     });
 
                     // Received section (ASCII and Hex views)
+                    let follow = self.auto_scroll && self.conn.paused.is_none();
                     ui.add_sized(ui.available_size(), |ui: &mut egui::Ui| {
                         egui::Frame::default()
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
-                                    ui.vertical(|ui| {
-                                        // ASCII view (Left side)
-                                        egui::ScrollArea::vertical()
-                                            //.max_height(f32::INFINITY)
-                                            .auto_shrink(false)
-                                            .max_width(ui.available_width() / 2.0)
-                                            .id_salt("ascii_view")
-                                            .show(ui, |ui| {
-                                                let rx = self.rx_buffer.lock().unwrap();
-                                                ui.monospace(rx.as_str());
-                                            });
-                                    });
-                                    ui.separator();
-
-                                    ui.vertical(|ui| {
-                                        // Hex view (Right side)
-                                        egui::ScrollArea::vertical()
-                                            .auto_shrink(false)
-                                            //.max_height(f32::INFINITY)
-                                            .max_width(ui.available_width())
-                                            .id_salt("hex_view")
-                                            .show(ui, |ui| {
-                                                let rx = self.rx_buffer.lock().unwrap();
-                                                let hex: String = rx
-                                                    .as_bytes()
-                                                    .chunks(8)
-                                                    .map(|chunk| {
-                                                        let hex_part: String = chunk
-                                                            .iter()
-                                                            .map(|b| format!("{:02X} ", b))
-                                                            .collect();
-                                                        let ascii_part: String = chunk
-                                                            .iter()
-                                                            .map(|b| {
-                                                                if b.is_ascii_graphic() {
-                                                                    *b as char
-                                                                } else {
-                                                                    '.'
-                                                                }
-                                                            })
-                                                            .collect();
-                                                        format!(
-                                                            "{:<24}  {}\n",
-                                                            hex_part, ascii_part
-                                                        )
-                                                    })
-                                                    .collect();
-                                                ui.monospace(hex);
-                                            });
-                                    });
+                                    // Both shown: the ASCII view gets `split` of the
+                                    // width, left of a draggable divider.
+                                    let total = ui.available_width();
+                                    if self.show_ascii {
+                                        let width = if self.show_hex {
+                                            total * self.split
+                                        } else {
+                                            total
+                                        };
+                                        ui.vertical(|ui| {
+                                            // ASCII view (Left side)
+                                            egui::ScrollArea::new([!self.wrap_ascii, true])
+                                                .auto_shrink(false)
+                                                .max_width(width)
+                                                .id_salt("ascii_view")
+                                                .stick_to_bottom(follow)
+                                                .show(ui, |ui| {
+                                                    self.ascii_view(ui);
+                                                    self.follow_tail(ui);
+                                                });
+                                        });
+                                    }
+                                    if self.show_ascii && self.show_hex {
+                                        self.split_divider(ui, total);
+                                    }
+                                    if self.show_hex {
+                                        ui.vertical(|ui| self.hex_view(ui, follow));
+                                    }
                                 });
                             })
                             .response
                     });
                 }
+                Mode::Timeline => {
+                    self.send_row(ui);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("Clear").clicked() {
+                            self.conn.timeline.clear();
+                        }
+                        ui.label("Time:");
+                        self.timestamp_mode_select(ui);
+                    });
+                    self.timeline_view(ui);
+                }
                 Mode::Terminal => {
-                    let rx = self.rx_buffer.lock().unwrap();
-                    let mut rx_clone = rx.clone(); // TextEdit needs a mutable String
+                    if !self.conn.connected {
+                        ui.colored_label(Color32::RED, "Not connected — typing is not sent");
+                    }
+                    ui.horizontal(|ui| {
+                        self.pause_toggle(ui);
+                        ui.checkbox(&mut self.local_echo, "Local echo")
+                            .on_hover_text("Show typed characters (display only, not logged)");
+                        ui.checkbox(&mut self.timestamps, "Timestamps");
+                        self.timestamp_mode_select(ui);
+                        ui.label("CR:");
+                        ComboBox::from_id_salt("carriage_return")
+                            .selected_text(self.carriage_return.label())
+                            .show_ui(ui, |ui| {
+                                for mode in CarriageReturn::ALL {
+                                    ui.selectable_value(
+                                        &mut self.carriage_return,
+                                        mode,
+                                        mode.label(),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text("What a carriage return without a newline does");
+                    });
+                    // Timestamps only go into this displayed copy, never into the
+                    // buffer itself.
+                    let raw: String = self.with_shown_rx(|rx| {
+                        if self.timestamps {
+                            let mut offset = rx.dropped;
+                            let mut text: String = rx
+                                .timed_lines()
+                                .map(|(time, line)| {
+                                    let stamp = format_stamp(
+                                        self.timestamp_mode,
+                                        *time,
+                                        self.conn.connected_at,
+                                    );
+                                    let end = offset + line.len() as u64;
+                                    let line = self.with_local_echo(line, offset, Some(end));
+                                    offset = end;
+                                    let line = decode(&line, self.encoding);
+                                    stamp
+                                        + apply_carriage_returns(&line, self.carriage_return)
+                                            .as_ref()
+                                })
+                                .collect();
+                            // Typed after the last received byte.
+                            let typed = decode(&self.with_local_echo(&[], offset, None), self.encoding);
+                            text += &apply_carriage_returns(&typed, self.carriage_return);
+                            text
+                        } else {
+                            let bytes = self.with_local_echo(&rx.bytes, rx.dropped, None);
+                            let text = decode(&bytes, self.encoding);
+                            apply_carriage_returns(&text, self.carriage_return).into_owned()
+                        }
+                    });
+                    // Color codes are turned into styles and taken out of the text.
+                    let (rx_clone, styles) = ansi::parse_ansi(&raw);
                     let id = ui.make_persistent_id("term");
+                    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+                    let text_color = if self.dark_mode {
+                        TERMINAL_TEXT_DARK
+                    } else {
+                        TERMINAL_TEXT_LIGHT
+                    };
+                    let highlights = &self.highlights;
+                    let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                        let mut job = highlight_job(
+                            text,
+                            &styles,
+                            highlights,
+                            &[],
+                            font_id.clone(),
+                            text_color,
+                        );
+                        job.wrap.max_width = wrap_width;
+                        ui.fonts(|f| f.layout_job(job))
+                    };
+                    // Scroll offsets live in egui memory under these ids, which eframe
+                    // persists, so each view keeps its place across mode switches and
+                    // restarts.
                     egui::ScrollArea::vertical()
-                        .stick_to_bottom(true)
+                        .stick_to_bottom(self.conn.paused.is_none())
+                        .id_salt("terminal_view")
                         .show(ui, |ui| {
+                            // A `&str` buffer can be selected and copied but not
+                            // edited; typed keys are sent to the device below.
                             ui.add(
-                                //todo: change to normal text so you can select, carefull with id, also change colors or something.
-                                egui::TextEdit::multiline(&mut rx_clone)
+                                egui::TextEdit::multiline(&mut rx_clone.as_str())
                                     .font(egui::TextStyle::Monospace)
                                     .hint_text("Terminal output")
                                     .desired_rows(20)
@@ -343,9 +1780,9 @@ impl App for UartApp {
                                     .lock_focus(true)
                                     .id(id)
                                     .code_editor()
-                                    .interactive(false)
-                                    .text_color_opt(Some(Color32::ORANGE)),
+                                    .layouter(&mut layouter),
                             );
+                            self.follow_tail(ui);
                         });
                     if !ui.ctx().memory_mut(|mem| mem.has_focus(id)) {
                         ui.ctx().memory_mut(|mem| mem.request_focus(id));
@@ -361,112 +1798,1982 @@ impl App for UartApp {
                             },
                         )
                     });
-                    ui.input(|i| {
-                        for event in &i.events {
-                            match event {
-                                egui::Event::Text(text) => {
-                                    // Send printable characters
-                                    self.send_to_uart(text.as_bytes());
-                                }
-                                egui::Event::Key {
-                                    key: egui::Key::Tab,
-                                    pressed: true,
-                                    ..
-                                } => {
-                                    // Send Tab explicitly
-                                    self.send_to_uart(&[b'\t']);
-                                }
-                                egui::Event::Key {
-                                    key: egui::Key::Enter,
-                                    pressed: true,
-                                    ..
-                                } => {
-                                    self.send_to_uart(&[b'\r', b'\n']);
-                                }
-                                egui::Event::Paste(text) => {
-                                    self.send_to_uart(text.as_bytes());
+                    if self.conn.connected {
+                        ui.input(|i| {
+                            for event in &i.events {
+                                match event {
+                                    egui::Event::Text(text) => {
+                                        // Send printable characters
+                                        self.send_typed(text.as_bytes());
+                                    }
+                                    egui::Event::Key {
+                                        key: egui::Key::Tab,
+                                        pressed: true,
+                                        ..
+                                    } => {
+                                        // Send Tab explicitly
+                                        self.send_typed(b"\t");
+                                    }
+                                    egui::Event::Key {
+                                        key: egui::Key::Enter,
+                                        pressed: true,
+                                        ..
+                                    } => {
+                                        self.send_typed(b"\r\n");
+                                    }
+                                    egui::Event::Paste(text) => {
+                                        self.send_typed(text.as_bytes());
+                                    }
+                                    _ => {}
                                 }
-                                _ => {}
                             }
+                        });
+                    } else if ui.input(|i| {
+                        i.events.iter().any(|event| {
+                            matches!(
+                                event,
+                                egui::Event::Text(_)
+                                    | egui::Event::Paste(_)
+                                    | egui::Event::Key {
+                                        key: egui::Key::Enter,
+                                        pressed: true,
+                                        ..
+                                    }
+                            )
+                        })
+                    }) {
+                        self.status = NOT_CONNECTED.to_string();
+                    }
+                }
+            }
+        });
+
+        self.clear_confirm_window(ctx);
+        self.autobaud_window(ctx);
+        self.errors_window(ctx);
+        self.counter_window(ctx);
+        self.handshake_window(ctx);
+        self.program_window(ctx);
+        self.send_file_window(ctx);
+        self.replay_to_port_window(ctx);
+        self.macros_window(ctx);
+        self.triggers_window(ctx);
+
+        self.script_windows(ctx);
+
+        self.jump_to_tail = false;
+        // While connected the display refreshes as often as the reader polls.
+        let refresh = match &self.conn.session {
+            Some(session) => session.settings().poll_interval,
+            None => Duration::from_millis(100),
+        };
+        ctx.request_repaint_after(refresh);
+    }
+}
+
+impl UartApp {
+    fn new(
+        tx: Sender<PathBuf>,
+        wnd_rx: Receiver<WndOp>,
+        port_rx: Receiver<PortRequest>,
+        send_rx: Receiver<Vec<u8>>,
+        uart_tx: Sender<Vec<u8>>,
+        rx_buffer: RxBuffer,
+        errors: ErrorCounters,
+    ) -> Self {
+        let trigger_table = TriggerTable::default();
+        let fired = trigger::spawn(
+            trigger_table.clone(),
+            rx_buffer.subscribe(),
+            TriggerSinks {
+                uart_tx,
+                script_tx: tx.clone(),
+            },
+        );
+        let mut app = Self {
+            conn: Connection {
+                rx_buffer,
+                ..Default::default()
+            },
+            errors,
+            script_ch: Some(tx),
+            window_chan: Some(wnd_rx),
+            port_chan: Some(port_rx),
+            send_chan: Some(send_rx),
+            trigger_table,
+            trigger_chan: Some(fired),
+            ..Default::default()
+        };
+        app.refresh_ports();
+        app
+    }
+
+    /// One tab per connection, plus buttons to open another and to close the
+    /// one shown. The first connection, which scripts use, can't be closed.
+    fn connection_tabs(&mut self, ui: &mut egui::Ui) {
+        let mut selected = self.active;
+        for (i, connection) in self.connections.iter().enumerate() {
+            let label = if i == self.active {
+                self.conn.label()
+            } else {
+                connection.label()
+            };
+            ui.selectable_value(&mut selected, i, format!("{}: {}", i + 1, label));
+        }
+        self.select_connection(selected);
+
+        if ui
+            .button("➕")
+            .on_hover_text("Open another connection")
+            .clicked()
+        {
+            let connection = Connection::default();
+            connection.rx_buffer.set_max_bytes(self.rx_max_bytes);
+            self.connections.push(connection);
+            self.select_connection(self.connections.len() - 1);
+        }
+        if self.active > 0
+            && ui
+                .button("✖")
+                .on_hover_text("Close this connection")
+                .clicked()
+        {
+            let closing = self.active;
+            self.select_connection(closing - 1);
+            self.abort_transfers(self.connections[closing].id);
+            // Dropping the connection stops its reader.
+            self.connections.remove(closing);
+        }
+    }
+
+    /// Shows connection `index`. The one shown lives in `conn`, so the two are
+    /// swapped through its slot.
+    fn select_connection(&mut self, index: usize) {
+        if index == self.active {
+            return;
+        }
+        std::mem::swap(&mut self.conn, &mut self.connections[self.active]);
+        std::mem::swap(&mut self.conn, &mut self.connections[index]);
+        self.active = index;
+        self.search_hit = 0;
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            mode: self.mode,
+            transport: self.conn.transport,
+            port_name: Some(self.conn.port_name.clone()).filter(|name| !name.is_empty()),
+            tcp_address: self.conn.tcp_address.clone(),
+            baud_rate: self.conn.baud_rate,
+            data_bits: self.conn.data_bits,
+            parity: self.conn.parity,
+            stop_bits: self.conn.stop_bits,
+            flow_control: self.conn.flow_control,
+            flush_interval_ms: self.flush_interval_ms,
+            read_timeout_ms: self.read_timeout_ms,
+            poll_interval_ms: self.poll_interval_ms,
+            read_chunk: self.read_chunk,
+            rx_max_bytes: self.rx_max_bytes,
+            auto_reconnect: self.auto_reconnect,
+            clear_on_connect: self.clear_on_connect,
+            break_ms: self.break_ms,
+            tx_hex: self.tx_hex,
+            tx_line_ending: self.tx_line_ending,
+            send_delay_ms: self.send_delay_ms,
+            echo_tx: self.echo_tx,
+            local_echo: self.local_echo,
+            non_printable: self.non_printable,
+            wrap_ascii: self.wrap_ascii,
+            encoding: self.encoding,
+            carriage_return: self.carriage_return,
+            auto_scroll: self.auto_scroll,
+            show_ascii: self.show_ascii,
+            show_hex: self.show_hex,
+            split: self.split,
+            hex_columns: self.hex_columns,
+            font_size: self.font_size,
+            dark_mode: self.dark_mode,
+            timestamps: self.timestamps,
+            timestamp_mode: self.timestamp_mode,
+            script_dir: self.script_dir.clone(),
+            firmware_dir: self.firmware_dir.clone(),
+            macros: self.macros.clone(),
+            triggers: self.triggers.clone(),
+            window_rects: self.window_rects.clone(),
+            log_format: self.log_format,
+        }
+    }
+
+    fn apply_config(&mut self, config: Config) {
+        self.mode = config.mode;
+        self.conn.transport = config.transport;
+        self.conn.port_name = config.port_name.unwrap_or_default();
+        self.conn.tcp_address = config.tcp_address;
+        self.conn.baud_rate = config.baud_rate;
+        self.conn.data_bits = config.data_bits;
+        self.conn.parity = config.parity;
+        self.conn.stop_bits = config.stop_bits;
+        self.conn.flow_control = config.flow_control;
+        self.flush_interval_ms = config.flush_interval_ms;
+        self.read_timeout_ms = config.read_timeout_ms;
+        self.poll_interval_ms = config.poll_interval_ms;
+        self.read_chunk = config.read_chunk;
+        self.rx_max_bytes = config.rx_max_bytes;
+        self.conn.rx_buffer.set_max_bytes(config.rx_max_bytes);
+        self.auto_reconnect = config.auto_reconnect;
+        self.clear_on_connect = config.clear_on_connect;
+        self.break_ms = config.break_ms;
+        self.tx_hex = config.tx_hex;
+        self.tx_line_ending = config.tx_line_ending;
+        self.send_delay_ms = config.send_delay_ms;
+        self.echo_tx = config.echo_tx;
+        self.local_echo = config.local_echo;
+        self.non_printable = config.non_printable;
+        self.wrap_ascii = config.wrap_ascii;
+        self.encoding = config.encoding;
+        self.carriage_return = config.carriage_return;
+        self.auto_scroll = config.auto_scroll;
+        self.show_ascii = config.show_ascii;
+        self.show_hex = config.show_hex;
+        self.split = config.split;
+        self.hex_columns = config.hex_columns;
+        self.font_size = config.font_size;
+        self.dark_mode = config.dark_mode;
+        self.timestamps = config.timestamps;
+        self.timestamp_mode = config.timestamp_mode;
+        self.script_dir = config.script_dir;
+        self.firmware_dir = config.firmware_dir;
+        self.macros = config.macros;
+        self.triggers = config.triggers;
+        self.window_rects = config.window_rects;
+        self.log_format = config.log_format;
+        self.trigger_table.set(self.triggers.clone());
+    }
+
+    fn stop_logging(&mut self) {
+        if let Some(session) = &self.conn.session {
+            session.set_log(None);
+        }
+        self.conn.log_file = None;
+    }
+
+    /// DTR and RTS toggles and the break button for the open port.
+    fn line_control_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Lines:");
+        let mut dtr = self.conn.dtr;
+        let mut rts = self.conn.rts;
+        let dtr_clicked = ui
+            .add_enabled_ui(self.conn.connected, |ui| ui.toggle_value(&mut dtr, "DTR"))
+            .inner
+            .clicked();
+        let rts_clicked = ui
+            .add_enabled_ui(self.conn.connected, |ui| ui.toggle_value(&mut rts, "RTS"))
+            .inner
+            .clicked();
+        ui.separator();
+        let break_clicked = ui
+            .add_enabled(self.conn.connected, egui::Button::new("Send Break"))
+            .clicked();
+        ui.add(
+            egui::DragValue::new(&mut self.break_ms)
+                .range(1..=10_000)
+                .suffix(" ms"),
+        );
+        let Some(session) = &self.conn.session else {
+            return;
+        };
+        if dtr_clicked {
+            match session.set_dtr(dtr) {
+                Ok(()) => self.conn.dtr = dtr,
+                Err(e) => self.status = format!("Failed to set DTR: {}", e),
+            }
+        }
+        if rts_clicked {
+            match session.set_rts(rts) {
+                Ok(()) => self.conn.rts = rts,
+                Err(e) => self.status = format!("Failed to set RTS: {}", e),
+            }
+        }
+        // The break is cleared on a background thread, so the UI keeps going.
+        if break_clicked && let Err(e) = session.send_break(Duration::from_millis(self.break_ms)) {
+            self.status = format!("Failed to send break: {}", e);
+        }
+    }
+
+    /// Collects how script runs went. A failure opens the Script output
+    /// panel, since nothing else would show it.
+    fn poll_script_reports(&mut self) {
+        let Some(ch) = &self.script_report_chan else {
+            return;
+        };
+        for event in ch.try_iter() {
+            let (path, text, failed) = match event {
+                ScriptEvent::Started(path) => (path, "started".to_string(), false),
+                ScriptEvent::Finished(path, took, 0) => (
+                    path,
+                    format!("finished in {:.1} s", took.as_secs_f64()),
+                    false,
+                ),
+                ScriptEvent::Finished(path, took, failures) => (
+                    path,
+                    format!(
+                        "finished in {:.1} s with {} failed assertion(s)",
+                        took.as_secs_f64(),
+                        failures
+                    ),
+                    true,
+                ),
+                ScriptEvent::Failed(path, error) => (path, format!("error: {}", error), true),
+            };
+            let name = path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy();
+            self.script_output.push(ScriptOutput {
+                text: format!("[{}] {}: {}", Local::now().format("%H:%M:%S"), name, text),
+                failed,
+            });
+            self.show_script_output |= failed;
+        }
+        let excess = self.script_output.len().saturating_sub(SCRIPT_OUTPUT_LINES);
+        self.script_output.drain(..excess);
+    }
+
+    /// Shows the windows scripts opened. Each is placed where the last window
+    /// of its name was left; several open under one name are staggered so
+    /// they don't cover each other.
+    fn script_windows(&mut self, ctx: &egui::Context) {
+        for (i, wnd) in self.windows.iter().enumerate() {
+            let twins = self.windows[..i]
+                .iter()
+                .filter(|other| other.name == wnd.name)
+                .count();
+            let stagger = egui::vec2(20.0, 20.0) * twins as f32;
+            // The id, not just the name, keeps windows of the same name apart.
+            let mut window = egui::Window::new(&wnd.name).id(egui::Id::new((
+                "script_window",
+                &wnd.name,
+                wnd.id,
+            )));
+            if let Some(rect) = self.window_rects.get(&wnd.name) {
+                window = window
+                    .default_pos(rect.min + stagger)
+                    .default_size(rect.size());
+            }
+            let shown = window.show(ctx, |ui| {
+                let content = ui.max_rect().size();
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        if wnd.monospace {
+                            ui.monospace(&wnd.text);
+                        } else {
+                            ui.label(&wnd.text);
                         }
                     });
+                content
+            });
+            // Kept as the corner of the frame and the size of the content,
+            // which is what `default_pos` and `default_size` take.
+            if let Some(shown) = shown
+                && let Some(content) = shown.inner
+                && twins == 0
+            {
+                let rect = egui::Rect::from_min_size(shown.response.rect.min, content);
+                self.window_rects.insert(wnd.name.clone(), rect);
+            }
+        }
+    }
+
+    /// Does what the Connect or Disconnect button would, or stops a pending
+    /// reconnect.
+    fn toggle_connection(&mut self) {
+        if self.conn.reconnect.is_some() {
+            self.disconnect("Reconnect cancelled");
+        } else if self.conn.connected {
+            self.disconnect("Disconnected");
+        } else if let Some(settings) = self.port_settings() {
+            if self.clear_on_connect {
+                self.clear_rx();
+            }
+            if let Err(e) = self.connect(&settings) {
+                self.errors.add(ErrorCategory::Open, 1);
+                let message = open_error_message(&settings.port_name, &e);
+                log::warn!("{}", message);
+                self.conn.last_error = Some(message);
+            }
+        } else {
+            self.status = match self.conn.transport {
+                TransportKind::Serial => "No port selected".to_string(),
+                TransportKind::Tcp => "No address entered".to_string(),
+            };
+        }
+    }
+
+    fn connect(&mut self, settings: &PortSettings) -> serialport::Result<()> {
+        let session = SerialSession::open(
+            settings,
+            self.conn.rx_buffer.clone(),
+            self.conn.traffic.clone(),
+            self.conn.timeline.clone(),
+        )?;
+        self.conn.session_events = Some(session.events());
+        session.set_log(self.conn.log_file.clone());
+        // Live data must not mix with a capture being played back.
+        self.replay = None;
+        self.conn.line_endings = LineEndingStats::default();
+        self.conn.connected_at = Local::now();
+        self.conn.session = Some(session);
+        self.conn.connected = true;
+        self.conn.reconnect = None;
+        self.conn.last_error = None;
+        self.status = format!("Connected to {}", settings.port_name);
+        Ok(())
+    }
+
+    /// Handles a port that stopped working: retries it when auto-reconnect is
+    /// on (logging carries on into the new session), disconnects otherwise.
+    fn connection_lost(&mut self, status: &str) {
+        log::warn!("{}", status);
+        match self.conn.session.take() {
+            Some(session) if self.auto_reconnect => {
+                self.conn.connected = false;
+                self.conn.session_events = None;
+                self.status = format!("{}, reconnecting", status);
+                self.conn.reconnect = Some(Reconnect {
+                    settings: session.settings(),
+                    attempts: 0,
+                    next_try: Instant::now() + RECONNECT_INTERVAL,
+                });
+            }
+            _ => self.disconnect(status),
+        }
+    }
+
+    fn tick_reconnect(&mut self, ctx: &egui::Context) {
+        let Some(reconnect) = &mut self.conn.reconnect else {
+            return;
+        };
+        if Instant::now() >= reconnect.next_try {
+            let settings = reconnect.settings.clone();
+            if self.connect(&settings).is_err()
+                && let Some(reconnect) = &mut self.conn.reconnect
+            {
+                reconnect.attempts += 1;
+                reconnect.next_try = Instant::now() + RECONNECT_INTERVAL;
+            }
+        }
+        ctx.request_repaint_after(RECONNECT_INTERVAL);
+    }
+
+    /// Starts the repeating script again once its interval has passed. Runs
+    /// may overlap if the script takes longer than the interval.
+    fn tick_script_repeat(&mut self, ctx: &egui::Context) {
+        let Some(repeat) = &mut self.script_repeat else {
+            return;
+        };
+        let now = Instant::now();
+        if now >= repeat.next_run {
+            if let Some(ch) = &self.script_ch {
+                let _ = ch.send(repeat.path.clone());
+            }
+            repeat.next_run = now + Duration::from_secs(self.script_every_s.max(1).into());
+        }
+        ctx.request_repaint_after(repeat.next_run - now);
+    }
+
+    /// Cancels the transfers running on connection `conn`, so they stop with
+    /// its port instead of failing on their next block.
+    fn abort_transfers(&self, conn: usize) {
+        for transfer in [
+            &self.program,
+            &self.send_file.transfer,
+            &self.replay_to_port.transfer,
+            &self.handshake.transfer,
+        ] {
+            if transfer.conn == conn {
+                transfer.abort.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn disconnect(&mut self, status: &str) {
+        self.abort_transfers(self.conn.id);
+        self.stop_logging();
+        self.conn.reconnect = None;
+        self.conn.connected = false;
+        self.conn.session = None;
+        self.conn.session_events = None;
+        self.status = status.to_string();
+    }
+
+    fn poll_session_events(&mut self) {
+        let Some(events) = &self.conn.session_events else {
+            return;
+        };
+        let mut gone = None;
+        let events: Vec<SessionEvent> = events.try_iter().collect();
+        for event in events {
+            match event {
+                SessionEvent::Data(bytes) => {
+                    self.conn.line_endings.feed(&bytes);
+                    if let Some(checker) = &mut self.counter.checker {
+                        let skipped = checker.feed(&bytes);
+                        if skipped > 0 {
+                            self.errors.add(ErrorCategory::Dropped, skipped);
+                        }
+                    }
+                }
+                SessionEvent::Sent(bytes) => {
+                    if self.echo_tx {
+                        // Shown in the ASCII view only; the received data stays as it was.
+                        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                        self.conn
+                            .push_marker(format!("<TX {}>", hex.join(" ")), MarkerKind::TxEcho);
+                    }
+                }
+                SessionEvent::LineError {
+                    message,
+                    marker,
+                    offset,
+                } => {
+                    self.conn
+                        .push_marker_at(offset, marker.to_string(), MarkerKind::LineError);
+                    self.errors.add(ErrorCategory::Framing, 1);
+                    self.status = format!("Line error: {}", message);
+                }
+                SessionEvent::Closed(e) => {
+                    self.errors.add(ErrorCategory::Read, 1);
+                    gone = Some(format!("Port closed: {}", e));
+                }
+                SessionEvent::WriteError {
+                    message,
+                    device_gone,
+                } => {
+                    self.errors.add(ErrorCategory::Write, 1);
+                    log::warn!("Write failed: {}", message);
+                    self.status = format!("Write failed: {}", message);
+                    if device_gone {
+                        gone = Some(format!("Device gone: {}", message));
+                    }
                 }
             }
-        });
+        }
+        if let Some(status) = gone {
+            self.connection_lost(&status);
+        }
+    }
+
+    /// Sets the monospace size used by the ASCII, hex and Terminal views.
+    fn apply_font_size(&self, ctx: &egui::Context) {
+        let current = ctx
+            .style()
+            .text_styles
+            .get(&egui::TextStyle::Monospace)
+            .map(|font| font.size);
+        if current != Some(self.font_size) {
+            ctx.style_mut(|style| {
+                style.text_styles.insert(
+                    egui::TextStyle::Monospace,
+                    FontId::monospace(self.font_size),
+                );
+            });
+        }
+    }
+
+    /// Puts the connected port in the window title so instances can be told
+    /// apart in the taskbar.
+    fn update_title(&mut self, ctx: &egui::Context) {
+        let title = match &self.conn.session {
+            Some(session) => {
+                let settings = session.settings();
+                format!(
+                    "UART Debug — {} @ {}",
+                    settings.port_name, settings.baud_rate
+                )
+            }
+            None => APP_TITLE.to_string(),
+        };
+        if title != self.title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+    }
+
+    /// Rescans the system ports. The chosen port stays chosen, listed or not.
+    fn refresh_ports(&mut self) {
+        match serialport::available_ports() {
+            Ok(ports) => {
+                self.ports = ports;
+                self.ports_error = None;
+            }
+            Err(e) => {
+                self.ports.clear();
+                self.ports_error = Some(format!("Cannot list serial ports: {}", e));
+            }
+        }
+    }
+
+    fn port_settings(&self) -> Option<PortSettings> {
+        let port_name = match self.conn.transport {
+            TransportKind::Serial if self.conn.port_name.trim().is_empty() => return None,
+            TransportKind::Serial => self.conn.port_name.trim().to_string(),
+            TransportKind::Tcp if self.conn.tcp_address.trim().is_empty() => return None,
+            TransportKind::Tcp => self.conn.tcp_address.trim().to_string(),
+        };
+        Some(PortSettings {
+            transport: self.conn.transport,
+            port_name,
+            baud_rate: self.conn.baud_rate,
+            data_bits: self.conn.data_bits,
+            parity: self.conn.parity,
+            stop_bits: self.conn.stop_bits,
+            flow_control: self.conn.flow_control,
+            flush_interval: Duration::from_millis(self.flush_interval_ms),
+            read_timeout: Duration::from_millis(self.read_timeout_ms),
+            poll_interval: Duration::from_millis(self.poll_interval_ms),
+            read_chunk: self.read_chunk,
+        })
+    }
+
+    /// Sends as many counter frames as the line can carry since the last tick.
+    fn tick_counter(&mut self, ctx: &egui::Context) {
+        let now = Instant::now();
+        let elapsed = now - self.counter.last_tick;
+        self.counter.last_tick = now;
+        if !self.counter.sending {
+            return;
+        }
+        if !self.conn.connected {
+            self.counter.sending = false;
+            return;
+        }
+
+        let test = &mut self.counter;
+        // Roughly ten bits per byte on the wire.
+        let budget = elapsed.as_secs_f64() * self.conn.baud_rate as f64 / 10.0;
+        let mut frames = (budget as usize / test.width.bytes()).max(1);
+        if test.count > 0 {
+            frames = frames.min(test.count.saturating_sub(test.sent));
+        }
+        let data = pattern::counter_frames(test.width, test.next, frames);
+        test.next += frames as u64;
+        test.sent += frames;
+        if test.count > 0 && test.sent >= test.count {
+            test.sending = false;
+        }
+        self.send_to_uart(&data);
+        ctx.request_repaint_after(Duration::from_millis(20));
+    }
+
+    fn counter_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.counter.open;
+        let connected = self.conn.connected;
+        egui::Window::new("Counter test")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let test = &mut self.counter;
+                ui.horizontal(|ui| {
+                    ui.label("Width:");
+                    ui.add_enabled_ui(!test.sending, |ui| {
+                        ComboBox::from_id_salt("counter_width")
+                            .selected_text(format!("{} bit", test.width.bytes() * 8))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut test.width, CounterWidth::U8, "8 bit");
+                                ui.selectable_value(&mut test.width, CounterWidth::U16, "16 bit");
+                                ui.selectable_value(&mut test.width, CounterWidth::U32, "32 bit");
+                            });
+                    });
+                    ui.label("Frames:");
+                    ui.add(egui::DragValue::new(&mut test.count))
+                        .on_hover_text("0 sends until stopped");
+                });
+                ui.horizontal(|ui| {
+                    if test.sending {
+                        if ui.button("Stop").clicked() {
+                            test.sending = false;
+                        }
+                    } else if ui
+                        .add_enabled(connected, egui::Button::new("Start"))
+                        .clicked()
+                    {
+                        test.sending = true;
+                        test.next = 0;
+                        test.sent = 0;
+                    }
+                    ui.label(format!("Sent {} frames", test.sent));
+                });
+
+                ui.separator();
+                let mut checking = test.checker.is_some();
+                ui.horizontal(|ui| {
+                    if ui.checkbox(&mut checking, "Check incoming").changed() {
+                        test.checker = checking.then(|| SequenceChecker::new(test.width));
+                    }
+                    if ui.button("Reset").clicked() && checking {
+                        test.checker = Some(SequenceChecker::new(test.width));
+                    }
+                });
+                if let Some(checker) = &test.checker {
+                    ui.label(format!("Frames: {}", checker.frames));
+                    let gaps = format!("Gaps: {}", checker.gaps);
+                    if checker.gaps > 0 {
+                        ui.colored_label(Color32::RED, gaps);
+                    } else {
+                        ui.label(gaps);
+                    }
+                    if let Some((expected, got)) = checker.last_gap {
+                        ui.label(format!("Last gap: expected {}, got {}", expected, got));
+                    }
+                }
+            });
+        self.counter.open = open;
+    }
+
+    fn start_programming(&mut self, path: &Path) {
+        if let Some(session) = &self.conn.session {
+            self.program
+                .start(path, PROGRAM_BLOCK, PROGRAM_GAP, session.writer());
+            self.program.conn = self.conn.id;
+        }
+    }
+
+    /// Shows the progress of "program device" while it runs and its outcome
+    /// until dismissed.
+    fn program_window(&mut self, ctx: &egui::Context) {
+        if self.program.poll(&self.errors) {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        if self.program.events.is_none() && self.program.result.is_empty() {
+            return;
+        }
+
+        let mut open = true;
+        egui::Window::new("Program device")
+            .open(&mut open)
+            .show(ctx, |ui| self.program.ui(ui));
+        // Closing dismisses the outcome; while sending the window stays up.
+        if !open {
+            self.program.result.clear();
+        }
+    }
+
+    /// Sends a file's bytes unchanged, in chunks of a chosen size and pace.
+    fn send_file_window(&mut self, ctx: &egui::Context) {
+        let state = &mut self.send_file;
+        if state.transfer.poll(&self.errors) {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        let running = state.transfer.events.is_some();
+        let mut open = state.open;
+        egui::Window::new("Send file")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let state = &mut self.send_file;
+                ui.add_enabled_ui(!running, |ui| {
+                    egui::Grid::new("send_file_grid").show(ui, |ui| {
+                        ui.label("Chunk size:");
+                        ui.add(
+                            egui::DragValue::new(&mut state.chunk)
+                                .range(1..=65_536)
+                                .suffix(" bytes"),
+                        );
+                        ui.end_row();
+                        ui.label("Delay between chunks:");
+                        ui.add(
+                            egui::DragValue::new(&mut state.gap_ms)
+                                .range(0..=10_000)
+                                .suffix(" ms"),
+                        );
+                        ui.end_row();
+                    });
+                });
+                let can_start = !running && self.conn.session.is_some();
+                if ui
+                    .add_enabled(can_start, egui::Button::new("Choose file..."))
+                    .on_disabled_hover_text("Connect to a port first")
+                    .clicked()
+                    && let Some(session) = &self.conn.session
+                    && let Some(path) = pick_file(&mut self.firmware_dir)
+                {
+                    state.transfer.start(
+                        &path,
+                        state.chunk,
+                        Duration::from_millis(state.gap_ms),
+                        session.writer(),
+                    );
+                    state.transfer.conn = self.conn.id;
+                }
+                if running || !state.transfer.result.is_empty() {
+                    state.transfer.ui(ui);
+                }
+            });
+        self.send_file.open = open;
+    }
+
+    fn replay_to_port_window(&mut self, ctx: &egui::Context) {
+        let state = &mut self.replay_to_port;
+        if state.transfer.poll(&self.errors) {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        let running = state.transfer.events.is_some();
+        let mut open = state.open;
+        egui::Window::new("Replay to port")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let state = &mut self.replay_to_port;
+                ui.add_enabled_ui(!running, |ui| {
+                    egui::Grid::new("replay_to_port_grid").show(ui, |ui| {
+                        ui.label("Send:");
+                        egui::ComboBox::from_id_salt("replay_direction")
+                            .selected_text(match state.direction {
+                                Direction::Rx => "Received data",
+                                Direction::Tx => "Sent data",
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut state.direction,
+                                    Direction::Rx,
+                                    "Received data",
+                                );
+                                ui.selectable_value(
+                                    &mut state.direction,
+                                    Direction::Tx,
+                                    "Sent data",
+                                );
+                            })
+                            .response
+                            .on_hover_text("Raw logs only hold received data");
+                        ui.end_row();
+                        ui.label("");
+                        ui.checkbox(&mut state.use_timing, "Use recorded timing")
+                            .on_hover_text("Only timestamped captures record timing");
+                        ui.end_row();
+                        ui.label("Chunk size:");
+                        ui.add(
+                            egui::DragValue::new(&mut state.chunk)
+                                .range(1..=65_536)
+                                .suffix(" bytes"),
+                        );
+                        ui.end_row();
+                        ui.label("Delay between chunks:");
+                        ui.add(
+                            egui::DragValue::new(&mut state.gap_ms)
+                                .range(0..=10_000)
+                                .suffix(" ms"),
+                        );
+                        ui.end_row();
+                    });
+                });
+                let can_start = !running && self.conn.session.is_some();
+                if ui
+                    .add_enabled(can_start, egui::Button::new("Choose capture..."))
+                    .on_disabled_hover_text("Connect to a port first")
+                    .clicked()
+                    && let Some(session) = &self.conn.session
+                    && let Some(path) = rfd::FileDialog::new().pick_file()
+                {
+                    match state.start(&path, session.writer()) {
+                        Ok(()) => state.transfer.conn = self.conn.id,
+                        Err(e) => {
+                            state.transfer.result = e.clone();
+                            self.status = e;
+                        }
+                    }
+                }
+                if running || !state.transfer.result.is_empty() {
+                    state.transfer.ui(ui);
+                }
+            });
+        self.replay_to_port.open = open;
+    }
+
+    fn handshake_window(&mut self, ctx: &egui::Context) {
+        if self.handshake.transfer.poll(&self.errors) {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        let mut open = self.handshake.open;
+        egui::Window::new("Handshake send")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let state = &mut self.handshake;
+                let running = state.transfer.events.is_some();
+                ui.add_enabled_ui(!running, |ui| {
+                    egui::Grid::new("handshake_settings").show(ui, |ui| {
+                        ui.label("Block size:");
+                        ui.add(egui::DragValue::new(&mut state.block_size).range(1..=65_536));
+                        ui.end_row();
+                        ui.label("ACK:");
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut state.ack);
+                            ui.checkbox(&mut state.ack_hex, "Hex");
+                        });
+                        ui.end_row();
+                        ui.label("Timeout:");
+                        ui.add(egui::DragValue::new(&mut state.timeout_ms).suffix(" ms"));
+                        ui.end_row();
+                        ui.label("Retries:");
+                        ui.add(egui::DragValue::new(&mut state.retries).range(0..=100));
+                        ui.end_row();
+                    });
+                });
+
+                let can_start = !running && self.conn.session.is_some();
+                if ui
+                    .add_enabled(can_start, egui::Button::new("Send file..."))
+                    .clicked()
+                    && let Some(session) = &self.conn.session
+                    && let Some(path) = pick_file(&mut self.firmware_dir)
+                {
+                    let ack = if state.ack_hex {
+                        parse_hex(&state.ack)
+                    } else {
+                        Ok(state.ack.as_bytes().to_vec())
+                    };
+                    match ack {
+                        Err(e) => state.transfer.result = format!("Invalid ACK: {}", e),
+                        Ok(ack) => {
+                            let config = HandshakeConfig {
+                                block_size: state.block_size,
+                                ack,
+                                timeout: Duration::from_millis(state.timeout_ms),
+                                retries: state.retries,
+                            };
+                            state.transfer.start_handshake(
+                                &path,
+                                config,
+                                session.writer(),
+                                self.conn.rx_buffer.subscribe(),
+                            );
+                            state.transfer.conn = self.conn.id;
+                        }
+                    }
+                }
+                if running || !state.transfer.result.is_empty() {
+                    state.transfer.ui(ui);
+                }
+            });
+        self.handshake.open = open;
+    }
 
-        if !self.windows.is_empty() {
-            for wnd in &self.windows {
-                egui::Window::new(&wnd.name).show(ctx, |ui| {
-                    ui.monospace(&wnd.text);
+    fn errors_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Errors")
+            .open(&mut self.show_errors)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("error_counts").show(ui, |ui| {
+                    for category in ErrorCategory::ALL.into_iter().filter(|c| c.is_event()) {
+                        ui.label(category.label());
+                        ui.label(self.errors.get(category).to_string());
+                        ui.end_row();
+                    }
                 });
+                // A byte count, so it isn't part of the error total.
+                ui.separator();
+                ui.label(format!(
+                    "{}: {}",
+                    ErrorCategory::Dropped.label(),
+                    self.errors.get(ErrorCategory::Dropped)
+                ));
+                if ui.button("Reset").clicked() {
+                    self.errors.reset();
+                }
+            });
+    }
+
+    fn autobaud_window(&mut self, ctx: &egui::Context) {
+        let state = &mut self.autobaud;
+        let mut done = false;
+        if let Some(events) = &state.events {
+            for event in events.try_iter() {
+                match event {
+                    AutobaudEvent::Response { baud_rate, data } => {
+                        let hex: Vec<String> = data.iter().map(|b| format!("{:02X}", b)).collect();
+                        state
+                            .log
+                            .push(format!("{}: [{}]", baud_rate, hex.join(" ")));
+                        if !data.is_empty() {
+                            state.answered_at = Some(baud_rate);
+                        }
+                    }
+                    AutobaudEvent::Failed { baud_rate, error } => {
+                        state.log.push(format!("{}: {}", baud_rate, error));
+                    }
+                    AutobaudEvent::Done => done = true,
+                }
+            }
+        }
+        if done {
+            state.events = None;
+            if state.answered_at.is_none() {
+                state.log.push("No response".to_string());
             }
         }
+        let running = state.events.is_some();
 
-        ctx.request_repaint_after(Duration::from_millis(100));
+        // Probing rates only makes sense on a real serial port.
+        let settings = self
+            .port_settings()
+            .filter(|s| s.transport == TransportKind::Serial);
+        let mut open = self.autobaud.open;
+        let mut use_rate = None;
+        egui::Window::new("Autobaud")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let state = &mut self.autobaud;
+                ui.horizontal(|ui| {
+                    ui.label("Character:");
+                    ui.add(egui::DragValue::new(&mut state.ch).hexadecimal(2, false, true));
+                    ui.checkbox(&mut state.cycle, "Cycle common rates");
+                });
+                let can_start = settings.is_some() && !self.conn.connected && !running;
+                let start = ui
+                    .add_enabled(can_start, egui::Button::new("Start"))
+                    .on_disabled_hover_text("Select a port and disconnect first");
+                if start.clicked()
+                    && let Some(settings) = &settings
+                {
+                    let rates = if state.cycle {
+                        autobaud::COMMON_BAUD_RATES.to_vec()
+                    } else {
+                        vec![self.conn.baud_rate]
+                    };
+                    state.log.clear();
+                    state.answered_at = None;
+                    state.events = Some(autobaud::spawn_autobaud(
+                        settings.clone(),
+                        state.ch,
+                        rates,
+                        Duration::from_millis(300),
+                    ));
+                }
+                for line in &state.log {
+                    ui.monospace(line);
+                }
+                if let Some(rate) = state.answered_at
+                    && ui.button(format!("Use {}", rate)).clicked()
+                {
+                    use_rate = Some(rate);
+                }
+            });
+        self.autobaud.open = open;
+        if let Some(rate) = use_rate {
+            self.conn.baud_rate = rate;
+        }
+        if running {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
     }
-}
 
-impl UartApp {
-    fn new(tx: Sender<PathBuf>, wnd_rx: Receiver<WndOp>) -> Self {
-        let mut new = UartApp::default();
-        new.script_ch = Some(tx);
-        new.window_chan = Some(wnd_rx);
+    /// Feeds the replay's due entries into the RX buffer as if they had just
+    /// arrived from the port.
+    fn tick_replay(&mut self, ctx: &egui::Context) {
+        let Some(state) = &mut self.replay else {
+            return;
+        };
+        let now = Instant::now();
+        let elapsed = now - state.last_tick;
+        state.last_tick = now;
+        if !state.playing {
+            return;
+        }
+        for entry in state.replay.advance(elapsed.mul_f32(state.speed)) {
+            self.conn.rx_buffer.push_bytes(&entry.data);
+        }
+        if state.replay.is_finished() {
+            state.playing = false;
+        } else {
+            ctx.request_repaint_after(Duration::from_millis(10));
+        }
+    }
 
-        new
+    fn replay_ui(&mut self, ui: &mut egui::Ui) {
+        let mut close = false;
+        if let Some(state) = &mut self.replay {
+            ui.horizontal(|ui| {
+                ui.label("Replay:");
+                let label = if state.playing { "⏸" } else { "▶" };
+                if ui.button(label).clicked() {
+                    state.playing = !state.playing;
+                }
+                ui.label("Speed:");
+                ui.add(
+                    egui::DragValue::new(&mut state.speed)
+                        .speed(0.1)
+                        .range(0.1..=100.0)
+                        .suffix("x"),
+                );
+                let mut pos = state.replay.position().as_secs_f64();
+                let end = state.replay.duration().as_secs_f64();
+                let scrubber = egui::Slider::new(&mut pos, 0.0..=end).suffix(" s");
+                if ui.add(scrubber).changed() {
+                    self.conn.rx_buffer.clear();
+                    for entry in state.replay.seek(Duration::from_secs_f64(pos)) {
+                        self.conn.rx_buffer.push_bytes(&entry.data);
+                    }
+                }
+                close = ui.button("Close").clicked();
+            });
+        }
+        if close {
+            self.replay = None;
+        }
+    }
+
+    /// Writes the received data to a file: a `.txt` file gets the hex dump as
+    /// shown in the hex view, anything else the raw bytes.
+    fn save_capture(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Raw bytes", &["bin"])
+            .add_filter("Hex dump", &["txt"])
+            .save_file()
+        else {
+            return;
+        };
+        let rx = self.conn.rx_buffer.lock();
+        let hex = path.extension().is_some_and(|ext| ext == "txt");
+        let result = if hex {
+            std::fs::write(&path, hex_dump(&rx.bytes, self.hex_columns, rx.dropped))
+        } else {
+            std::fs::write(&path, &rx.bytes)
+        };
+        drop(rx);
+        self.status = match result {
+            Ok(()) => format!("Saved capture to {}", path.display()),
+            Err(e) => format!("Failed to save capture: {}", e),
+        };
     }
 
-    fn send_to_uart(&self, data: &[u8]) {
-        if let Some(ref port) = self.port_handle {
-            let port = Arc::clone(port);
-            let data = data.to_vec();
-            std::thread::spawn(move || {
-                let mut port = port.lock().unwrap();
-                let _ = port.write_all(&data);
+    /// Empties the received data along with everything derived from it: the
+    /// hex view offsets, search hits, the selection, line ending counts, byte
+    /// counters, the timeline and markers.
+    fn clear_rx(&mut self) {
+        self.conn.clear_rx();
+        self.search_hit = 0;
+        self.search_matches = 0;
+        self.scroll_to_hit = false;
+        self.selection.clear();
+    }
+
+    fn clear_confirm_window(&mut self, ctx: &egui::Context) {
+        if !self.confirm_clear {
+            return;
+        }
+        let size = self.conn.rx_buffer.lock().bytes.len();
+        egui::Window::new("Clear received data?")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "This discards {} KiB of received data.",
+                    size / 1024
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Clear").clicked() {
+                        self.clear_rx();
+                        self.confirm_clear = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_clear = false;
+                    }
+                });
             });
+    }
+
+    /// Writes the received data in the layout of `xxd`, so it can be compared
+    /// with dumps made by other tools.
+    fn export_hexdump(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Hex dump", &["txt", "hex"])
+            .save_file()
+        else {
+            return;
+        };
+        let dump = xxd_dump(&self.conn.rx_buffer.lock().bytes);
+        self.status = match std::fs::write(&path, dump) {
+            Ok(()) => format!("Exported hexdump to {}", path.display()),
+            Err(e) => format!("Failed to export hexdump: {}", e),
+        };
+    }
+
+    /// Runs `f` on the received data as it should be displayed: the live
+    /// buffer, or the snapshot taken when the display was paused.
+    fn with_shown_rx<R>(&self, f: impl FnOnce(&RxData) -> R) -> R {
+        match &self.conn.paused {
+            Some(frozen) => f(frozen),
+            None => f(&self.conn.rx_buffer.lock()),
         }
     }
-}
 
-use std::sync::atomic::{AtomicUsize, Ordering};
-fn main() -> eframe::Result<()> {
-//todo: get the uart receive stuff outside of the graphics logic and treat it the same as a script. with is receive buffer copy and the send channel. you have a chatgpt started with the way to handle the buffer copies.
-    let options = eframe::NativeOptions::default();
-    let (tx, rx) = mpsc::channel::<PathBuf>();
-    let (wnd_tx, wnd_rx) = mpsc::channel::<WndOp>();
-    let app = UartApp::new(tx, wnd_rx);
-    let next_id = Arc::new(AtomicUsize::new(0)); // <- unique ID generator
-    let clone_tx = move || wnd_tx.clone();
-
-    thread::spawn(move || {
-        let next_id = Arc::clone(&next_id);
-        while let Ok(script) = rx.recv() {
-            let tx = clone_tx();
-            let tx1 = clone_tx();
-            let tx2 = clone_tx();
-            let next_id = Arc::clone(&next_id);
-            thread::spawn(move || {
-                let mut engine = Engine::new();
-
-                engine.register_fn("new_window", move |name: String| -> usize {
-                    //create_wnd(name);
-                    let id = next_id.fetch_add(1, Ordering::Relaxed);
-                    let _ = tx.send(WndOp::New(id, name));
-                    id
+    fn pause_toggle(&mut self, ui: &mut egui::Ui) {
+        let mut paused = self.conn.paused.is_some();
+        if ui
+            .toggle_value(&mut paused, "⏸ Pause")
+            .on_hover_text("Freeze the display; data keeps being received")
+            .clicked()
+        {
+            if paused {
+                self.conn.paused = Some(self.conn.rx_buffer.lock().clone());
+            } else {
+                self.conn.paused = None;
+                self.jump_to_tail = true;
+            }
+        }
+    }
+
+    /// Scrolls the current view to its end right after the display resumes.
+    fn follow_tail(&self, ui: &mut egui::Ui) {
+        if self.jump_to_tail {
+            ui.scroll_to_cursor(Some(egui::Align::BOTTOM));
+        }
+    }
+
+    fn timestamp_mode_select(&mut self, ui: &mut egui::Ui) {
+        ComboBox::from_id_salt("timestamp_mode")
+            .selected_text(match self.timestamp_mode {
+                TimestampMode::WallClock => "Wall clock",
+                TimestampMode::Relative => "Since connect",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut self.timestamp_mode,
+                    TimestampMode::WallClock,
+                    "Wall clock",
+                );
+                ui.selectable_value(
+                    &mut self.timestamp_mode,
+                    TimestampMode::Relative,
+                    "Since connect",
+                );
+            });
+    }
+
+    /// One row per line sent or received, in the order they happened, with
+    /// its time and an arrow for the direction.
+    fn timeline_view(&self, ui: &mut egui::Ui) {
+        let (rx_color, tx_color) = if self.dark_mode {
+            (TERMINAL_TEXT_DARK, TIMELINE_TX_DARK)
+        } else {
+            (TERMINAL_TEXT_LIGHT, TIMELINE_TX_LIGHT)
+        };
+        let entries = self.conn.timeline.lock();
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .id_salt("timeline_view")
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, entries.len(), |ui, rows| {
+                for entry in &entries[rows] {
+                    let line = entry.bytes.strip_suffix(b"\n").unwrap_or(&entry.bytes);
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+                    let text = format!(
+                        "{}{} {}",
+                        format_stamp(self.timestamp_mode, entry.time, self.conn.connected_at),
+                        entry.dir.arrow(),
+                        render_ascii(line, self.non_printable, self.encoding)
+                    );
+                    let color = match entry.dir {
+                        Direction::Rx => rx_color,
+                        Direction::Tx => tx_color,
+                    };
+                    ui.add(
+                        egui::Label::new(egui::RichText::new(text).monospace().color(color))
+                            .extend(),
+                    );
+                }
+            });
+    }
+
+    /// The bar between the ASCII and hex views; dragging it moves `split`.
+    fn split_divider(&mut self, ui: &mut egui::Ui, total: f32) {
+        let size = egui::vec2(8.0, ui.available_height());
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+        let response = response.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+        if response.dragged() && total > 0.0 {
+            self.split = (self.split + response.drag_delta().x / total).clamp(0.1, 0.9);
+        }
+        let stroke = if response.hovered() || response.dragged() {
+            ui.visuals().widgets.hovered.fg_stroke
+        } else {
+            ui.visuals().widgets.noninteractive.bg_stroke
+        };
+        ui.painter().vline(rect.center().x, rect.y_range(), stroke);
+    }
+
+    fn hex_view(&mut self, ui: &mut egui::Ui, follow: bool) {
+        ComboBox::from_id_salt("hex_columns")
+            .selected_text(format!("{} bytes/row", self.hex_columns))
+            .show_ui(ui, |ui| {
+                for columns in [8, 16, 32] {
+                    ui.selectable_value(&mut self.hex_columns, columns, columns.to_string());
+                }
+            });
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .max_width(ui.available_width())
+            .id_salt("hex_view")
+            .stick_to_bottom(follow)
+            .show(ui, |ui| {
+                let hex =
+                    self.with_shown_rx(|rx| hex_dump(&rx.bytes, self.hex_columns, rx.dropped));
+                ui.monospace(hex);
+                self.follow_tail(ui);
+            });
+    }
+
+    fn ascii_view(&mut self, ui: &mut egui::Ui) {
+        let (shown, marker_lines) = self.with_shown_rx(|rx| self.render_with_markers(rx));
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let color = ui.visuals().text_color();
+        let hits = find_all(&shown, &self.search, self.search_ignore_case);
+        self.search_matches = hits.len();
+        if self.search_hit >= hits.len() {
+            self.search_hit = 0;
+        }
+        let found: Vec<(Range<usize>, Color32)> = hits
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let color = if i == self.search_hit {
+                    SEARCH_CURRENT
+                } else {
+                    SEARCH_OTHER
+                };
+                (r.clone(), color)
+            })
+            .chain(marker_lines)
+            .collect();
+        let highlights = &self.highlights;
+        let wrap = self.wrap_ascii;
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            let mut job = highlight_job(text, &[], highlights, &found, font_id.clone(), color);
+            job.wrap.max_width = if wrap { wrap_width } else { f32::INFINITY };
+            ui.fonts(|f| f.layout_job(job))
+        };
+        // Read-only, but selectable for "Highlight selection".
+        let output = egui::TextEdit::multiline(&mut shown.as_str())
+            .desired_width(f32::INFINITY)
+            .frame(false)
+            .layouter(&mut layouter)
+            .show(ui);
+        if self.scroll_to_hit
+            && let Some(hit) = hits.get(self.search_hit)
+        {
+            let index = shown[..hit.start].chars().count();
+            let rect = output.galley.pos_from_ccursor(CCursor::new(index));
+            ui.scroll_to_rect(
+                rect.translate(output.galley_pos.to_vec2()),
+                Some(egui::Align::Center),
+            );
+        }
+        self.scroll_to_hit = false;
+        if let Some(range) = output.cursor_range
+            && !range.is_empty()
+        {
+            self.selection = range.slice_str(&shown).to_string();
+        }
+    }
+
+    /// Renders `rx` for the ASCII view with a line for every marker at its
+    /// place in the data. Also returns where those lines are in the text,
+    /// with their background.
+    fn render_with_markers(&self, rx: &RxData) -> (String, Vec<(Range<usize>, Color32)>) {
+        let mut shown = String::new();
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for marker in &self.conn.markers {
+            // Markers in data that has been dropped go with it.
+            let Some(at) = marker.offset.checked_sub(rx.dropped) else {
+                continue;
+            };
+            let mut at = (at as usize).clamp(start, rx.bytes.len());
+            while rx.bytes.get(at).is_some_and(|&b| b & 0xC0 == 0x80) {
+                at += 1;
+            }
+            shown += &render_ascii(&rx.bytes[start..at], self.non_printable, self.encoding);
+            start = at;
+            if !shown.is_empty() && !shown.ends_with('\n') {
+                shown.push('\n');
+            }
+            let line_start = shown.len();
+            let background = match marker.kind {
+                MarkerKind::Label => {
+                    shown += &format!("▶ {}", marker.label);
+                    MARKER_BACKGROUND
+                }
+                MarkerKind::TxEcho => {
+                    shown += &marker.label;
+                    TX_ECHO_BACKGROUND
+                }
+                MarkerKind::LocalEcho => {
+                    shown += &format!("<typed {}>", marker.label.escape_debug());
+                    TX_ECHO_BACKGROUND
+                }
+                MarkerKind::LineError => {
+                    shown += &marker.label;
+                    LINE_ERROR_BACKGROUND
+                }
+            };
+            lines.push((line_start..shown.len(), background));
+            shown.push('\n');
+        }
+        shown += &render_ascii(&rx.bytes[start..], self.non_printable, self.encoding);
+        (shown, lines)
+    }
+
+    /// Splices the local echo typed within stream offsets `start..end` into
+    /// `bytes`, which begin at `start`. With no `end`, everything typed from
+    /// `start` on goes in.
+    fn with_local_echo<'a>(&self, bytes: &'a [u8], start: u64, end: Option<u64>) -> Cow<'a, [u8]> {
+        let mut echoes = self
+            .conn
+            .markers
+            .iter()
+            .filter(|m| m.kind == MarkerKind::LocalEcho && m.offset >= start)
+            .filter(|m| end.is_none_or(|end| m.offset < end))
+            .peekable();
+        if echoes.peek().is_none() {
+            return Cow::Borrowed(bytes);
+        }
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut at = 0;
+        for echo in echoes {
+            let split = ((echo.offset - start) as usize).clamp(at, bytes.len());
+            out.extend_from_slice(&bytes[at..split]);
+            out.extend_from_slice(echo.label.as_bytes());
+            at = split;
+        }
+        out.extend_from_slice(&bytes[at..]);
+        Cow::Owned(out)
+    }
+
+    /// Marks the current end of the received data with `marker_label`.
+    fn insert_marker(&mut self) {
+        let offset = {
+            let rx = self.conn.rx_buffer.lock();
+            rx.dropped + rx.bytes.len() as u64
+        };
+        let label = match self.marker_label.trim() {
+            "" => {
+                let placed = self.conn.markers.iter();
+                let count = placed.filter(|m| m.kind == MarkerKind::Label).count();
+                format!("Marker {}", count + 1)
+            }
+            label => label.to_string(),
+        };
+        self.conn.markers.push(Marker {
+            offset,
+            label,
+            kind: MarkerKind::Label,
+        });
+        self.marker_label.clear();
+    }
+
+    fn search_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Find:");
+        if ui.text_edit_singleline(&mut self.search).changed() {
+            self.search_hit = 0;
+            self.scroll_to_hit = true;
+        }
+        if ui
+            .checkbox(&mut self.search_ignore_case, "Ignore case")
+            .changed()
+        {
+            self.search_hit = 0;
+        }
+        let count = self.search_matches;
+        if ui.add_enabled(count > 0, egui::Button::new("◀")).clicked() {
+            self.search_hit = (self.search_hit + count - 1) % count;
+            self.scroll_to_hit = true;
+        }
+        if ui.add_enabled(count > 0, egui::Button::new("▶")).clicked() {
+            self.search_hit = (self.search_hit + 1) % count;
+            self.scroll_to_hit = true;
+        }
+        if !self.search.is_empty() {
+            if count == 0 {
+                ui.label("No matches");
+            } else {
+                ui.label(format!("{} of {}", self.search_hit + 1, count));
+            }
+        }
+    }
+
+    fn highlights_ui(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        for (i, h) in self.highlights.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgba(&mut h.color);
+                ui.text_edit_singleline(&mut h.keyword);
+                if ui.small_button("✖").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.highlights.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_highlight);
+            if ui.button("Add").clicked() && !self.new_highlight.is_empty() {
+                let keyword = std::mem::take(&mut self.new_highlight);
+                self.add_highlight(keyword);
+            }
+        });
+        if ui
+            .add_enabled(
+                !self.selection.is_empty(),
+                egui::Button::new("Highlight selection"),
+            )
+            .clicked()
+        {
+            self.add_highlight(self.selection.clone());
+        }
+    }
+
+    /// The Send field with its options and the Send button.
+    fn send_row(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let payload = if self.tx_hex {
+                parse_hex(&self.tx_buffer)
+            } else {
+                Ok(self.tx_buffer.as_bytes().to_vec())
+            };
+            let mut input = egui::TextEdit::singleline(&mut self.tx_buffer);
+            if payload.is_err() {
+                input = input.text_color(Color32::RED);
+            }
+            let input = ui.add(input);
+            if input.has_focus() {
+                self.recall_history(ui);
+            }
+            // Enter in the field sends just like the button.
+            let submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            ui.checkbox(&mut self.tx_hex, "Hex")
+                .on_hover_text("Send whitespace-separated hex bytes, e.g. 0A FF 7E");
+            ComboBox::from_id_salt("tx_line_ending")
+                .selected_text(
+                    self.tx_line_ending
+                        .map_or("None".to_string(), |e| e.to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.tx_line_ending, None, "None");
+                    for ending in [LineEnding::Lf, LineEnding::Cr, LineEnding::CrLf] {
+                        ui.selectable_value(
+                            &mut self.tx_line_ending,
+                            Some(ending),
+                            ending.to_string(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Line ending appended on Send");
+            let send = ui
+                .add_enabled(
+                    self.conn.connected && payload.is_ok(),
+                    egui::Button::new("Send"),
+                )
+                .on_disabled_hover_text("Connect to a port and enter valid data");
+            if (send.clicked() || (submitted && self.conn.connected))
+                && let Ok(mut data) = payload.clone()
+            {
+                if submitted {
+                    input.request_focus();
+                }
+                if let Some(ending) = self.tx_line_ending {
+                    data.extend_from_slice(ending.as_bytes());
+                }
+                self.send_to_uart(&data);
+                self.remember_sent();
+            }
+            if !self.conn.connected {
+                ui.colored_label(Color32::RED, "Not connected");
+            }
+            if let Err(e) = &payload {
+                ui.colored_label(Color32::RED, e);
+            }
+            ui.checkbox(&mut self.echo_tx, "Echo TX")
+                .on_hover_text("Show the bytes that went out, tagged as TX, in the ASCII view");
+        });
+    }
+
+    /// One button per macro; each sends its payload with the line ending
+    /// chosen for the send field.
+    fn macro_buttons(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        for m in &self.macros {
+            let payload = m.bytes();
+            let button = ui
+                .add_enabled(
+                    self.conn.connected && payload.is_ok(),
+                    egui::Button::new(&m.label),
+                )
+                .on_hover_text(&m.payload);
+            let button = match &payload {
+                Err(e) => button.on_disabled_hover_text(e),
+                Ok(_) => button.on_disabled_hover_text("Connect to a port first"),
+            };
+            if button.clicked() {
+                clicked = payload.ok();
+            }
+        }
+        if let Some(mut data) = clicked {
+            if let Some(ending) = self.tx_line_ending {
+                data.extend_from_slice(ending.as_bytes());
+            }
+            self.send_to_uart(&data);
+        }
+        if ui.button("Edit macros...").clicked() {
+            self.edit_macros = true;
+        }
+        if ui.button("Triggers...").clicked() {
+            self.edit_triggers = true;
+        }
+    }
+
+    fn macros_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.edit_macros;
+        egui::Window::new("Macros").open(&mut open).show(ctx, |ui| {
+            let mut remove = None;
+            egui::Grid::new("macros").show(ui, |ui| {
+                ui.label("Label");
+                ui.label("Payload");
+                ui.end_row();
+                for (i, m) in self.macros.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(&mut m.label).desired_width(100.0));
+                    let valid = m.bytes().is_ok();
+                    let mut input = egui::TextEdit::singleline(&mut m.payload);
+                    if !valid {
+                        input = input.text_color(Color32::RED);
+                    }
+                    ui.add(input);
+                    ui.checkbox(&mut m.hex, "Hex");
+                    if ui.small_button("✖").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                self.macros.remove(i);
+            }
+            if ui.button("Add").clicked() {
+                self.macros.push(Macro {
+                    label: format!("Macro {}", self.macros.len() + 1),
+                    payload: String::new(),
+                    hex: false,
                 });
-                engine.register_fn("write_wnd", move |id: usize, text: String| {
-                    //create_wnd(name);
-                    let _ = tx1.send(WndOp::WriteText(id, text));
+            }
+        });
+        self.edit_macros = open;
+    }
+
+    /// Editor for the trigger rules, which only watch the first connection,
+    /// and a log of the ones that fired.
+    fn triggers_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.edit_triggers;
+        egui::Window::new("Triggers")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let mut remove = None;
+                egui::Grid::new("triggers").show(ui, |ui| {
+                    ui.label("On");
+                    ui.label("When a line contains");
+                    ui.label("Action");
+                    ui.end_row();
+                    for (i, t) in self.triggers.iter_mut().enumerate() {
+                        ui.checkbox(&mut t.enabled, "");
+                        ui.add(egui::TextEdit::singleline(&mut t.pattern).desired_width(150.0));
+                        let kind = match t.action {
+                            TriggerAction::Send(_) => "Send",
+                            TriggerAction::RunScript(_) => "Run script",
+                            TriggerAction::Notify => "Log only",
+                        };
+                        ComboBox::from_id_salt(("trigger_action", i))
+                            .selected_text(kind)
+                            .show_ui(ui, |ui| {
+                                if ui.selectable_label(kind == "Send", "Send").clicked()
+                                    && kind != "Send"
+                                {
+                                    t.action = TriggerAction::Send(String::new());
+                                }
+                                if ui
+                                    .selectable_label(kind == "Run script", "Run script")
+                                    .clicked()
+                                    && kind != "Run script"
+                                {
+                                    t.action = TriggerAction::RunScript(PathBuf::new());
+                                }
+                                if ui
+                                    .selectable_label(kind == "Log only", "Log only")
+                                    .clicked()
+                                {
+                                    t.action = TriggerAction::Notify;
+                                }
+                            });
+                        match &mut t.action {
+                            TriggerAction::Send(text) => {
+                                let valid = parse_escaped(text).is_ok();
+                                let mut input = egui::TextEdit::singleline(text).hint_text("y\\n");
+                                if !valid {
+                                    input = input.text_color(Color32::RED);
+                                }
+                                ui.add(input)
+                                    .on_hover_text("Escapes: \\n \\r \\t \\0 \\\\ \\xNN");
+                            }
+                            TriggerAction::RunScript(path) => {
+                                let name = path
+                                    .file_name()
+                                    .map_or("(none)".into(), |n| n.to_string_lossy());
+                                if ui
+                                    .button(name)
+                                    .on_hover_text(path.display().to_string())
+                                    .clicked()
+                                    && let Some(picked) = pick_file(&mut self.script_dir)
+                                {
+                                    *path = picked;
+                                }
+                            }
+                            TriggerAction::Notify => {
+                                ui.label("");
+                            }
+                        }
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
                 });
-                if let Err(e) = engine.run_file(script) {
-                    eprintln!("Rhai Error: {}", e);
+                if let Some(i) = remove {
+                    self.triggers.remove(i);
+                }
+                if ui.button("Add").clicked() {
+                    self.triggers.push(Trigger {
+                        enabled: true,
+                        pattern: String::new(),
+                        action: TriggerAction::Send(String::new()),
+                    });
                 }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(150.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for entry in &self.trigger_log {
+                            ui.monospace(entry);
+                        }
+                    });
             });
+        if self.edit_triggers {
+            self.trigger_table.set(self.triggers.clone());
+        }
+        self.edit_triggers = open;
+    }
+
+    fn add_highlight(&mut self, keyword: String) {
+        let color = HIGHLIGHT_PALETTE[self.highlights.len() % HIGHLIGHT_PALETTE.len()];
+        self.highlights.push(Highlight { keyword, color });
+    }
+
+    /// Steps through the send history with the Up and Down keys.
+    fn recall_history(&mut self, ui: &egui::Ui) {
+        let (up, down) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+            )
+        });
+        let Some(last) = self.tx_history.len().checked_sub(1) else {
+            return;
+        };
+        let index = match (up, down, self.history_index) {
+            (true, _, None) => Some(last),
+            (true, _, Some(i)) => Some(i.saturating_sub(1)),
+            (false, true, Some(i)) if i < last => Some(i + 1),
+            (false, true, Some(_)) => None,
+            _ => return,
+        };
+        self.history_index = index;
+        self.tx_buffer = index
+            .map(|i| self.tx_history[i].clone())
+            .unwrap_or_default();
+    }
+
+    fn remember_sent(&mut self) {
+        if self.tx_history.last() != Some(&self.tx_buffer) {
+            self.tx_history.push(self.tx_buffer.clone());
+            if self.tx_history.len() > TX_HISTORY_LEN {
+                self.tx_history.remove(0);
+            }
         }
-    });
+        self.history_index = None;
+    }
+
+    /// Sends terminal input, showing it right away when local echo is on. The
+    /// echo only goes to the display, not to the received data, the log or
+    /// scripts.
+    fn send_typed(&mut self, data: &[u8]) {
+        self.send_to_uart(data);
+        if self.local_echo {
+            let text = String::from_utf8_lossy(data).into_owned();
+            self.conn.push_marker(text, MarkerKind::LocalEcho);
+        }
+    }
+
+    fn send_to_uart(&mut self, data: &[u8]) {
+        match &self.conn.session {
+            Some(session) => session.write_paced(data, Duration::from_millis(self.send_delay_ms)),
+            None => self.status = NOT_CONNECTED.to_string(),
+        }
+    }
+}
+
+/// Asks for a file to open, starting in `dir` and remembering the folder
+/// the file came from there for next time.
+fn pick_file(dir: &mut Option<PathBuf>) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new();
+    if let Some(dir) = dir {
+        dialog = dialog.set_directory(dir);
+    }
+    let path = dialog.pick_file()?;
+    *dir = path.parent().map(Path::to_path_buf);
+    Some(path)
+}
+
+/// Lays out `text` in the colors of `styles` (runs from [`ansi::parse_ansi`],
+/// `color` where none applies) with every highlight keyword drawn on its
+/// background color. `found` marks search hits, which are drawn over keywords.
+fn highlight_job(
+    text: &str,
+    styles: &[(Range<usize>, AnsiStyle)],
+    highlights: &[Highlight],
+    found: &[(Range<usize>, Color32)],
+    font_id: FontId,
+    color: Color32,
+) -> LayoutJob {
+    let keywords: Vec<&str> = highlights.iter().map(|h| h.keyword.as_str()).collect();
+    let marks = find_keywords(text, &keywords);
+    // Styles only fit the text they were parsed from.
+    let styles = match styles.last() {
+        Some((range, _)) if range.end != text.len() => &[],
+        _ => styles,
+    };
+
+    let mut cuts = vec![0, text.len()];
+    cuts.extend(styles.iter().flat_map(|(r, _)| [r.start, r.end]));
+    cuts.extend(marks.iter().flat_map(|(r, _)| [r.start, r.end]));
+    cuts.extend(found.iter().flat_map(|(r, _)| [r.start, r.end]));
+    cuts.retain(|&cut| cut <= text.len());
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut job = LayoutJob::default();
+    let (mut s, mut m, mut f) = (0, 0, 0);
+    for cut in cuts.windows(2) {
+        let (start, end) = (cut[0], cut[1]);
+        while styles.get(s).is_some_and(|(r, _)| r.end <= start) {
+            s += 1;
+        }
+        while marks.get(m).is_some_and(|(r, _)| r.end <= start) {
+            m += 1;
+        }
+        while found.get(f).is_some_and(|(r, _)| r.end <= start) {
+            f += 1;
+        }
+        let style = styles
+            .get(s)
+            .filter(|(r, _)| r.start <= start)
+            .map(|(_, style)| *style)
+            .unwrap_or_default();
+        let mut format = TextFormat::simple(font_id.clone(), ansi_color(style, color));
+        if let Some((r, hit)) = found.get(f)
+            && r.start <= start
+        {
+            format.background = *hit;
+        } else if let Some((r, i)) = marks.get(m)
+            && r.start <= start
+        {
+            format.background = highlights[*i].color;
+        }
+        job.append(&text[start..end], 0.0, format);
+    }
+    job
+}
+
+/// The color text in `style` is drawn in. Bold is shown as a brighter shade.
+fn ansi_color(style: AnsiStyle, default: Color32) -> Color32 {
+    let color = match style.fg {
+        None => default,
+        Some(AnsiColor::Black) => Color32::DARK_GRAY,
+        Some(AnsiColor::Red) => Color32::RED,
+        Some(AnsiColor::Green) => Color32::GREEN,
+        Some(AnsiColor::Yellow) => Color32::YELLOW,
+        Some(AnsiColor::Blue) => Color32::from_rgb(80, 140, 255),
+        Some(AnsiColor::Magenta) => Color32::from_rgb(220, 80, 220),
+        Some(AnsiColor::Cyan) => Color32::from_rgb(0, 200, 200),
+        Some(AnsiColor::White) => Color32::LIGHT_GRAY,
+    };
+    if style.bold {
+        color.lerp_to_gamma(Color32::WHITE, 0.4)
+    } else {
+        color
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    //todo: get the uart receive stuff outside of the graphics logic and treat it the same as a script. with is receive buffer copy and the send channel. you have a chatgpt started with the way to handle the buffer copies.
+    match headless::parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => std::process::exit(headless::run(&args)),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}\n{}", e, headless::USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    // Release builds on Windows have no console, so diagnostics go to a file.
+    let recent_log = logging::init(Some(logging::default_log_path()), false);
+    let options = eframe::NativeOptions::default();
+    let (wnd_tx, wnd_rx) = mpsc::channel::<WndOp>();
+    let (port_tx, port_rx) = mpsc::channel::<PortRequest>();
+    let (status_tx, status_rx) = mpsc::channel::<Sender<PortStatus>>();
+    let (uart_tx, send_rx) = mpsc::channel::<Vec<u8>>();
+    let rx_buffer = RxBuffer::new();
+    let errors = ErrorCounters::new();
+    let (script_report_tx, script_report_rx) = mpsc::channel::<ScriptEvent>();
+    let tx = script::spawn_runner(
+        ScriptHost {
+            wnd_tx,
+            port_tx,
+            uart_tx: uart_tx.clone(),
+            status_tx,
+            rx_buffer: rx_buffer.clone(),
+            errors: errors.clone(),
+        },
+        script_report_tx,
+    );
+    let mut app = UartApp {
+        recent_log,
+        status_chan: Some(status_rx),
+        script_report_chan: Some(script_report_rx),
+        ..UartApp::new(tx, wnd_rx, port_rx, send_rx, uart_tx, rx_buffer, errors)
+    };
 
     eframe::run_native(
-        "UART Debug Tool",
+        APP_TITLE,
         options,
-        Box::new(|_cc| Ok(Box::new(app))),
+        Box::new(|cc| {
+            if let Some(config) = cc
+                .storage
+                .and_then(|storage| eframe::get_value::<Config>(storage, CONFIG_KEY))
+            {
+                app.apply_config(config);
+            }
+            Ok(Box::new(app))
+        }),
     )
 }
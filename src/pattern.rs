@@ -0,0 +1,110 @@
+/// Size of one counter frame. Frames are little-endian and wrap at the
+/// width's maximum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl CounterWidth {
+    pub fn bytes(self) -> usize {
+        match self {
+            CounterWidth::U8 => 1,
+            CounterWidth::U16 => 2,
+            CounterWidth::U32 => 4,
+        }
+    }
+
+    fn mask(self) -> u64 {
+        (1u64 << (8 * self.bytes())) - 1
+    }
+}
+
+/// Encodes `count` consecutive counter frames starting at `start`.
+pub fn counter_frames(width: CounterWidth, start: u64, count: usize) -> Vec<u8> {
+    (0..count as u64)
+        .flat_map(|i| {
+            let value = (start + i) & width.mask();
+            value.to_le_bytes().into_iter().take(width.bytes())
+        })
+        .collect()
+}
+
+/// Checks that a received counter stream has no gaps.
+///
+/// The first complete frame sets the expected value; every later frame that
+/// isn't the previous one plus one counts as a gap.
+pub struct SequenceChecker {
+    width: CounterWidth,
+    partial: Vec<u8>,
+    expected: Option<u64>,
+    pub frames: u64,
+    pub gaps: u64,
+    /// Expected and received values of the most recent gap.
+    pub last_gap: Option<(u64, u64)>,
+}
+
+impl SequenceChecker {
+    pub fn new(width: CounterWidth) -> Self {
+        Self {
+            width,
+            partial: Vec::new(),
+            expected: None,
+            frames: 0,
+            gaps: 0,
+            last_gap: None,
+        }
+    }
+
+    /// Checks the frames completed by `bytes`. Returns how many bytes the
+    /// gaps among them skipped, taking each gap as the counter jumping ahead.
+    pub fn feed(&mut self, bytes: &[u8]) -> u64 {
+        let mut skipped = 0;
+        self.partial.extend_from_slice(bytes);
+        let n = self.width.bytes();
+        let whole = self.partial.len() / n * n;
+        for frame in self.partial[..whole].chunks(n) {
+            let mut le = [0u8; 8];
+            le[..n].copy_from_slice(frame);
+            let value = u64::from_le_bytes(le);
+            if let Some(expected) = self.expected
+                && value != expected
+            {
+                self.gaps += 1;
+                self.last_gap = Some((expected, value));
+                skipped += (value.wrapping_sub(expected) & self.width.mask()) * n as u64;
+            }
+            self.expected = Some((value + 1) & self.width.mask());
+            self.frames += 1;
+        }
+        self.partial.drain(..whole);
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_wraps_without_a_gap() {
+        let mut checker = SequenceChecker::new(CounterWidth::U8);
+        let frames = counter_frames(CounterWidth::U8, 254, 4);
+        assert_eq!(frames, [254, 255, 0, 1]);
+        assert_eq!(checker.feed(&frames), 0);
+        assert_eq!((checker.frames, checker.gaps), (4, 0));
+    }
+
+    #[test]
+    fn gap_across_the_wrap_counts_skipped_bytes() {
+        let mut checker = SequenceChecker::new(CounterWidth::U16);
+        let mut stream = counter_frames(CounterWidth::U16, 0xFFFE, 1);
+        stream.extend(counter_frames(CounterWidth::U16, 1, 1));
+        // Frames may be split across chunks.
+        assert_eq!(checker.feed(&stream[..3]), 0);
+        assert_eq!(checker.feed(&stream[3..]), 2 * 2);
+        assert_eq!(checker.gaps, 1);
+        assert_eq!(checker.last_gap, Some((0xFFFF, 1)));
+    }
+}
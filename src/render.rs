@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// How the ASCII view shows bytes that have no printable form.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NonPrintable {
+    Hide,
+    Dot,
+    /// `\xNN`, one per byte.
+    Escape,
+    /// C escapes such as `\r`, `\0` or `\e` where there is one, `\xNN`
+    /// otherwise. Newlines and tabs are shown this way too; a newline still
+    /// ends the line.
+    CStyle,
+}
+
+/// How received bytes are turned into characters for the text views.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1: every byte is the character with the same code.
+    Latin1,
+    /// 7-bit ASCII; bytes from 0x80 up count as invalid.
+    Ascii,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 3] = [Encoding::Utf8, Encoding::Latin1, Encoding::Ascii];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Ascii => "ASCII",
+        }
+    }
+}
+
+/// What a carriage return not followed by a newline does in the Terminal view.
+/// `\r\n` is always a single line break.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CarriageReturn {
+    /// Goes back to the start of the line, so what follows overwrites it, as
+    /// on a real terminal. Progress bars rely on this.
+    #[default]
+    Overwrite,
+    /// Starts a new line.
+    Newline,
+    /// Is dropped.
+    Ignore,
+}
+
+impl CarriageReturn {
+    pub const ALL: [CarriageReturn; 3] = [
+        CarriageReturn::Overwrite,
+        CarriageReturn::Newline,
+        CarriageReturn::Ignore,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CarriageReturn::Overwrite => "Overwrite line",
+            CarriageReturn::Newline => "New line",
+            CarriageReturn::Ignore => "Ignore",
+        }
+    }
+}
+
+/// Applies `mode` to every carriage return in `text`, leaving no `\r` behind.
+pub fn apply_carriage_returns(text: &str, mode: CarriageReturn) -> Cow<'_, str> {
+    if !text.contains('\r') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match mode {
+            CarriageReturn::Overwrite => {
+                let mut shown: Vec<char> = Vec::new();
+                let mut column = 0;
+                for c in line.chars() {
+                    if c == '\r' {
+                        column = 0;
+                        continue;
+                    }
+                    match shown.get_mut(column) {
+                        Some(slot) => *slot = c,
+                        None => shown.push(c),
+                    }
+                    column += 1;
+                }
+                out.extend(shown);
+            }
+            CarriageReturn::Newline => out.push_str(&line.replace('\r', "\n")),
+            CarriageReturn::Ignore => out.extend(line.chars().filter(|&c| c != '\r')),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Decodes `bytes` as is, with invalid bytes replaced by U+FFFD.
+pub fn decode(bytes: &[u8], encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        Encoding::Ascii => bytes
+            .iter()
+            .map(|&b| {
+                if b.is_ascii() {
+                    b as char
+                } else {
+                    char::REPLACEMENT_CHARACTER
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Decodes received bytes for display. Printable characters are kept, and so
+/// are newlines and tabs unless `mode` is [`NonPrintable::CStyle`]; control
+/// characters and bytes that aren't valid in `encoding` are rendered
+/// according to `mode`.
+pub fn render_ascii(bytes: &[u8], mode: NonPrintable, encoding: Encoding) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    let put = |out: &mut String, raw: &[u8]| match mode {
+        NonPrintable::Hide => {}
+        NonPrintable::Dot => out.extend(raw.iter().map(|_| '.')),
+        NonPrintable::Escape => {
+            for b in raw {
+                let _ = write!(out, "\\x{:02X}", b);
+            }
+        }
+        NonPrintable::CStyle => {
+            for &b in raw {
+                match c_escape(b) {
+                    Some(name) => out.push_str(name),
+                    None => {
+                        let _ = write!(out, "\\x{:02X}", b);
+                    }
+                }
+            }
+            if raw == b"\n" {
+                out.push('\n');
+            }
+        }
+    };
+    let keep_whitespace = mode != NonPrintable::CStyle;
+    let put_char = |out: &mut String, c: char, raw: &[u8]| {
+        if (keep_whitespace && (c == '\n' || c == '\t')) || !c.is_control() {
+            out.push(c);
+        } else {
+            put(out, raw);
+        }
+    };
+    match encoding {
+        Encoding::Utf8 => {
+            for chunk in bytes.utf8_chunks() {
+                for c in chunk.valid().chars() {
+                    let mut raw = [0u8; 4];
+                    put_char(&mut out, c, c.encode_utf8(&mut raw).as_bytes());
+                }
+                put(&mut out, chunk.invalid());
+            }
+        }
+        Encoding::Latin1 => {
+            for b in bytes {
+                put_char(&mut out, *b as char, std::slice::from_ref(b));
+            }
+        }
+        Encoding::Ascii => {
+            for b in bytes {
+                if b.is_ascii() {
+                    put_char(&mut out, *b as char, std::slice::from_ref(b));
+                } else {
+                    put(&mut out, std::slice::from_ref(b));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn c_escape(b: u8) -> Option<&'static str> {
+    Some(match b {
+        0x00 => "\\0",
+        0x07 => "\\a",
+        0x08 => "\\b",
+        b'\t' => "\\t",
+        b'\n' => "\\n",
+        0x0B => "\\v",
+        0x0C => "\\f",
+        b'\r' => "\\r",
+        0x1B => "\\e",
+        _ => return None,
+    })
+}
+
+/// Formats bytes exactly like `xxd` does by default: offsets from zero, 16
+/// bytes per row in groups of two, and the printable ASCII at the end.
+pub fn xxd_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let mut hex_part = String::new();
+        for (i, b) in chunk.iter().enumerate() {
+            if i > 0 && i % 2 == 0 {
+                hex_part.push(' ');
+            }
+            let _ = write!(hex_part, "{:02x}", b);
+        }
+        let ascii_part: String = chunk
+            .iter()
+            .map(|&b| {
+                if (0x20..0x7F).contains(&b) {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "{:08x}: {:<39}  {}", row * 16, hex_part, ascii_part);
+    }
+    out
+}
+
+/// Formats bytes as hex rows of `columns` bytes, each prefixed with its
+/// offset (counting from `start`) and followed by its ASCII form with `.` for
+/// anything not printable.
+pub fn hex_dump(bytes: &[u8], columns: usize, start: u64) -> String {
+    let columns = columns.max(1);
+    let width = columns * 3;
+    let mut out = String::new();
+    for (row, chunk) in bytes.chunks(columns).enumerate() {
+        let offset = start + (row * columns) as u64;
+        let hex_part: String = chunk.iter().map(|b| format!("{:02X} ", b)).collect();
+        let ascii_part: String = chunk
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "{:08X}: {:<width$}  {}", offset, hex_part, ascii_part);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xxd_dump_matches_xxd_layout() {
+        let dump = xxd_dump(b"0123456789abcdef\x00\x7f~");
+        assert_eq!(
+            dump,
+            "00000000: 3031 3233 3435 3637 3839 6162 6364 6566  0123456789abcdef\n\
+             00000010: 007f 7e                                  ..~\n"
+        );
+        assert_eq!(xxd_dump(b""), "");
+    }
+
+    #[test]
+    fn carriage_return_overwrites_from_line_start() {
+        let text = "loading 10%\rloading 100%\r\ndone\rD";
+        assert_eq!(
+            apply_carriage_returns(text, CarriageReturn::Overwrite),
+            "loading 100%\nDone"
+        );
+        // A shorter rewrite leaves the tail of the longer text.
+        assert_eq!(
+            apply_carriage_returns("abcdef\rxy", CarriageReturn::Overwrite),
+            "xycdef"
+        );
+    }
+
+    #[test]
+    fn carriage_return_newline_and_ignore() {
+        let text = "a\rb\r\nc";
+        assert_eq!(
+            apply_carriage_returns(text, CarriageReturn::Newline),
+            "a\nb\nc"
+        );
+        assert_eq!(
+            apply_carriage_returns(text, CarriageReturn::Ignore),
+            "ab\nc"
+        );
+        assert!(matches!(
+            apply_carriage_returns("no returns\n", CarriageReturn::Overwrite),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,526 @@
+use crate::buffer::RxBuffer;
+use crate::errors::{ErrorCategory, ErrorCounters};
+use crate::session::{PortOp, SerialSession};
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, NativeCallContext};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Requests from scripts to whoever owns the windows.
+pub enum WndOp {
+    New(usize, String),
+    WriteText(usize, String),
+    Close(usize),
+    /// Switches a window between monospace (`true`) and proportional text.
+    SetFont(usize, bool),
+    /// Sets how many bytes of text a window keeps; older text is dropped.
+    SetLimit(usize, usize),
+    /// Empties the received data and everything shown from it.
+    ClearRx,
+}
+
+/// A [`PortOp`] from a script. When `reply` is set the script waits for the
+/// outcome.
+pub struct PortRequest {
+    pub op: PortOp,
+    pub reply: Option<Sender<Result<(), String>>>,
+}
+
+impl PortRequest {
+    fn new(op: PortOp) -> Self {
+        Self { op, reply: None }
+    }
+
+    /// Reports the outcome back to the script, if it asked for one.
+    pub fn finish(self, result: Result<(), String>) {
+        if let Some(reply) = self.reply {
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// The connection as a script sees it, from [`ScriptHost::status_tx`].
+#[derive(Clone, Debug, Default)]
+pub struct PortStatus {
+    pub connected: bool,
+    pub port_name: String,
+    pub baud_rate: u32,
+}
+
+impl PortStatus {
+    pub fn of(session: Option<&SerialSession>) -> Self {
+        match session {
+            Some(session) => {
+                let settings = session.settings();
+                Self {
+                    connected: true,
+                    port_name: settings.port_name,
+                    baud_rate: settings.baud_rate,
+                }
+            }
+            None => Self::default(),
+        }
+    }
+}
+
+/// Channels a script uses to reach the rest of the tool.
+#[derive(Clone)]
+pub struct ScriptHost {
+    pub wnd_tx: Sender<WndOp>,
+    pub port_tx: Sender<PortRequest>,
+    /// Bytes scripts want written to the port.
+    pub uart_tx: Sender<Vec<u8>>,
+    /// Asks for the current [`PortStatus`], which is sent back on the
+    /// enclosed channel.
+    pub status_tx: Sender<Sender<PortStatus>>,
+    pub rx_buffer: RxBuffer,
+    /// Scripts that fail to run are counted here.
+    pub errors: ErrorCounters,
+}
+
+/// Builds an engine with the whole script API registered. Window ids come
+/// from `next_id` and failed assertions are counted in `failures`.
+pub fn new_engine(
+    host: &ScriptHost,
+    next_id: Arc<AtomicUsize>,
+    failures: Arc<AtomicUsize>,
+) -> Engine {
+    let mut engine = Engine::new();
+    register_window_api(&mut engine, host.wnd_tx.clone(), next_id);
+    register_assert_api(&mut engine, failures);
+    register_uart_api(&mut engine, &host.rx_buffer, host.wnd_tx.clone());
+    register_port_api(&mut engine, host.port_tx.clone());
+    register_send_api(&mut engine, host.uart_tx.clone());
+    register_status_api(&mut engine, host.status_tx.clone());
+    engine.register_fn("sleep_ms", |ms: i64| {
+        thread::sleep(Duration::from_millis(ms.max(0) as u64));
+    });
+    engine
+}
+
+/// Registers the window functions (`new_window`, `new_window_styled`,
+/// `set_monospace`, `set_wnd_limit`, `write_wnd`, `close_wnd`) on `engine`.
+/// Ids come from `next_id` so they stay unique across scripts.
+pub fn register_window_api(engine: &mut Engine, wnd_tx: Sender<WndOp>, next_id: Arc<AtomicUsize>) {
+    let tx = wnd_tx.clone();
+    let ids = Arc::clone(&next_id);
+    engine.register_fn("new_window", move |name: String| -> usize {
+        let id = ids.fetch_add(1, Ordering::Relaxed);
+        let _ = tx.send(WndOp::New(id, name));
+        id
+    });
+    let tx = wnd_tx.clone();
+    engine.register_fn(
+        "new_window_styled",
+        move |name: String, monospace: bool| -> usize {
+            let id = next_id.fetch_add(1, Ordering::Relaxed);
+            let _ = tx.send(WndOp::New(id, name));
+            let _ = tx.send(WndOp::SetFont(id, monospace));
+            id
+        },
+    );
+    let tx = wnd_tx.clone();
+    engine.register_fn("set_monospace", move |id: usize, monospace: bool| {
+        let _ = tx.send(WndOp::SetFont(id, monospace));
+    });
+    let tx = wnd_tx.clone();
+    engine.register_fn("set_wnd_limit", move |id: usize, max_len: usize| {
+        let _ = tx.send(WndOp::SetLimit(id, max_len));
+    });
+    let tx = wnd_tx.clone();
+    engine.register_fn("write_wnd", move |id: usize, text: String| {
+        let _ = tx.send(WndOp::WriteText(id, text));
+    });
+    engine.register_fn("close_wnd", move |id: usize| {
+        let _ = wnd_tx.send(WndOp::Close(id));
+    });
+}
+
+/// Registers `assert(cond, msg)` and `fail(msg)`. Every failure is logged and
+/// counted in `failures`, so callers can turn it into a verdict.
+pub fn register_assert_api(engine: &mut Engine, failures: Arc<AtomicUsize>) {
+    let count = Arc::clone(&failures);
+    engine.register_fn("assert", move |cond: bool, msg: String| {
+        if !cond {
+            count.fetch_add(1, Ordering::Relaxed);
+            log::error!("Assertion failed: {}", msg);
+        }
+    });
+    engine.register_fn("fail", move |msg: String| {
+        failures.fetch_add(1, Ordering::Relaxed);
+        log::error!("Failed: {}", msg);
+    });
+}
+
+/// A script's own position in the received stream.
+struct RxCursor {
+    source: RxBuffer,
+    /// Subscribed on the first read, so scripts that never read don't queue
+    /// up everything received.
+    feed: Option<Receiver<Vec<u8>>>,
+    pending: VecDeque<u8>,
+}
+
+impl RxCursor {
+    fn feed(&mut self) -> &Receiver<Vec<u8>> {
+        let source = &self.source;
+        self.feed.get_or_insert_with(|| source.subscribe())
+    }
+
+    /// Waits until at least `n` bytes are pending or `deadline` passes.
+    fn fill(&mut self, n: usize, deadline: Instant) {
+        while self.pending.len() < n {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.feed().recv_timeout(left) {
+                Ok(chunk) => self.pending.extend(chunk),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Forgets everything received so far.
+    fn clear(&mut self) {
+        if let Some(feed) = &self.feed {
+            while feed.try_recv().is_ok() {}
+        }
+        self.pending.clear();
+    }
+
+    fn take(&mut self, n: usize) -> Vec<u8> {
+        let n = n.min(self.pending.len());
+        self.pending.drain(..n).collect()
+    }
+
+    /// Waits until something is pending or `deadline` passes, then returns
+    /// everything received so far. A UTF-8 sequence still missing bytes at the
+    /// end stays pending, so it isn't split across two reads.
+    fn read_available(&mut self, deadline: Instant) -> Vec<u8> {
+        while complete_utf8_len(self.pending.make_contiguous()) == 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.feed().recv_timeout(left) {
+                Ok(chunk) => self.pending.extend(chunk),
+                Err(_) => break,
+            }
+        }
+        while let Ok(chunk) = self.feed().try_recv() {
+            self.pending.extend(chunk);
+        }
+        let n = complete_utf8_len(self.pending.make_contiguous());
+        self.take(n)
+    }
+
+    /// Waits for a full line and returns it without its terminator, or
+    /// `None` if `deadline` passes first. A partial line stays pending.
+    fn read_line(&mut self, deadline: Instant) -> Option<String> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+                let mut line = self.take(end + 1);
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Some(String::from_utf8_lossy(&line).into_owned());
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.feed().recv_timeout(left) {
+                Ok(chunk) => self.pending.extend(chunk),
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// The length of `bytes` without a UTF-8 sequence cut off at the end, which
+/// the next chunk may still complete.
+fn complete_utf8_len(bytes: &[u8]) -> usize {
+    let len = bytes.len();
+    for back in 1..=len.min(3) {
+        let b = bytes[len - back];
+        if b & 0xC0 == 0x80 {
+            continue;
+        }
+        let needed = match b {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if needed > back { len - back } else { len };
+    }
+    len
+}
+
+/// Registers the receive-side functions on `engine`:
+///
+/// - `expect_file(path, timeout_ms) -> bool` compares the next received bytes
+///   against the contents of `path`.
+/// - `uart_read(timeout_ms) -> String` returns everything received since the
+///   last read, waiting up to the timeout for something to arrive.
+/// - `uart_read_line(timeout_ms) -> String` returns the next received line,
+///   or an empty string if none completes in time.
+/// - `uart_clear()` drops whatever the script hasn't read yet and asks, via
+///   `wnd_tx`, for the received data and the display to be emptied.
+/// - `uart_on_line(callback, timeout_ms) -> int` calls `callback(line)` for
+///   every received line until the callback returns `false` or no line comes
+///   within the timeout, and returns how many lines it handled. Lines are
+///   queued from its first call on, separately from the reads above.
+///
+/// The reads only see data that arrives after the first of them, as nothing
+/// is queued for a script that doesn't read. They use their own
+/// subscription, so the display keeps getting everything no matter what the
+/// script is waiting on.
+pub fn register_uart_api(engine: &mut Engine, rx_buffer: &RxBuffer, wnd_tx: Sender<WndOp>) {
+    let cursor = Rc::new(RefCell::new(RxCursor {
+        source: rx_buffer.clone(),
+        feed: None,
+        pending: VecDeque::new(),
+    }));
+
+    let reads = Rc::clone(&cursor);
+    engine.register_fn("uart_read", move |timeout_ms: i64| -> String {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+        let bytes = reads.borrow_mut().read_available(deadline);
+        String::from_utf8_lossy(&bytes).into_owned()
+    });
+
+    let clears = Rc::clone(&cursor);
+    engine.register_fn("uart_clear", move || {
+        clears.borrow_mut().clear();
+        let _ = wnd_tx.send(WndOp::ClearRx);
+    });
+
+    let source = rx_buffer.clone();
+    let line_feed: RefCell<Option<Rc<Receiver<String>>>> = RefCell::default();
+    engine.register_fn(
+        "uart_on_line",
+        move |context: NativeCallContext,
+              callback: FnPtr,
+              timeout_ms: i64|
+              -> Result<i64, Box<EvalAltResult>> {
+            // Not borrowed across the callbacks, which may call this again.
+            let line_feed = Rc::clone(
+                line_feed
+                    .borrow_mut()
+                    .get_or_insert_with(|| Rc::new(source.subscribe_lines())),
+            );
+            let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+            let mut handled = 0;
+            while let Ok(line) = line_feed.recv_timeout(timeout) {
+                handled += 1;
+                let keep_going: Dynamic = callback.call_within_context(&context, (line,))?;
+                if keep_going.as_bool() == Ok(false) {
+                    break;
+                }
+            }
+            Ok(handled)
+        },
+    );
+
+    let lines = Rc::clone(&cursor);
+    engine.register_fn("uart_read_line", move |timeout_ms: i64| -> String {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+        lines.borrow_mut().read_line(deadline).unwrap_or_default()
+    });
+
+    engine.register_fn(
+        "expect_file",
+        move |path: String, timeout_ms: i64| -> bool {
+            let expected = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    log::error!("expect_file: cannot read {}: {}", path, e);
+                    return false;
+                }
+            };
+            let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+            let mut cursor = cursor.borrow_mut();
+            cursor.fill(expected.len(), deadline);
+            let got = cursor.take(expected.len());
+
+            match expected.iter().zip(&got).position(|(a, b)| a != b) {
+                Some(offset) => {
+                    log::error!(
+                        "expect_file: mismatch at offset {}: expected {:02X}, got {:02X}",
+                        offset,
+                        expected[offset],
+                        got[offset]
+                    );
+                    false
+                }
+                None if got.len() < expected.len() => {
+                    log::error!(
+                        "expect_file: timed out at offset {} of {}",
+                        got.len(),
+                        expected.len()
+                    );
+                    false
+                }
+                None => true,
+            }
+        },
+    );
+}
+
+/// Registers the line-control functions `set_dtr(bool)`, `set_rts(bool)`,
+/// `send_break(ms)`, `set_baud(rate)` and `uart_flush()`. They are carried
+/// out by whoever owns the session.
+///
+/// `set_baud` and `uart_flush` wait for the outcome and return an empty
+/// string on success or the reason it failed, e.g. when no port is connected.
+/// `uart_flush` returns once everything sent before it is on the wire.
+pub fn register_port_api(engine: &mut Engine, port_tx: Sender<PortRequest>) {
+    let tx = port_tx.clone();
+    engine.register_fn("set_dtr", move |level: bool| {
+        let _ = tx.send(PortRequest::new(PortOp::SetDtr(level)));
+    });
+    let tx = port_tx.clone();
+    engine.register_fn("set_rts", move |level: bool| {
+        let _ = tx.send(PortRequest::new(PortOp::SetRts(level)));
+    });
+    let tx = port_tx.clone();
+    engine.register_fn("send_break", move |ms: i64| {
+        let duration = Duration::from_millis(ms.max(0) as u64);
+        let _ = tx.send(PortRequest::new(PortOp::Break(duration)));
+    });
+    let tx = port_tx.clone();
+    engine.register_fn("set_baud", move |rate: i64| -> String {
+        let Some(rate) = u32::try_from(rate).ok().filter(|&r| r > 0) else {
+            return format!("invalid baud rate: {}", rate);
+        };
+        request_and_wait(&tx, PortOp::SetBaud(rate), Duration::from_secs(5))
+    });
+    engine.register_fn("uart_flush", move || -> String {
+        // Everything sent at a slow rate may take a while to drain.
+        request_and_wait(&port_tx, PortOp::Flush, Duration::from_secs(60))
+    });
+}
+
+/// Sends `op` and waits for its outcome: an empty string on success, the
+/// error otherwise.
+fn request_and_wait(port_tx: &Sender<PortRequest>, op: PortOp, timeout: Duration) -> String {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    let request = PortRequest {
+        op,
+        reply: Some(reply_tx),
+    };
+    if port_tx.send(request).is_err() {
+        return "no one is handling port operations".to_string();
+    }
+    match reply_rx.recv_timeout(timeout) {
+        Ok(Ok(())) => String::new(),
+        Ok(Err(e)) => e,
+        Err(_) => "no reply to port operation".to_string(),
+    }
+}
+
+/// Registers `is_connected()`, `get_port_name()` and `get_baud()`, which
+/// report the connection as it is when called. A status that can't be had
+/// reads as not connected.
+pub fn register_status_api(engine: &mut Engine, status_tx: Sender<Sender<PortStatus>>) {
+    let query = move || {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        if status_tx.send(reply_tx).is_err() {
+            return PortStatus::default();
+        }
+        reply_rx
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap_or_default()
+    };
+    let status = Rc::new(query);
+    let query = Rc::clone(&status);
+    engine.register_fn("is_connected", move || query().connected);
+    let query = Rc::clone(&status);
+    engine.register_fn("get_port_name", move || query().port_name);
+    engine.register_fn("get_baud", move || i64::from(status().baud_rate));
+}
+
+/// Registers `uart_send(text)`, which queues the text's bytes for the port.
+pub fn register_send_api(engine: &mut Engine, uart_tx: Sender<Vec<u8>>) {
+    engine.register_fn("uart_send", move |text: String| {
+        let _ = uart_tx.send(text.into_bytes());
+    });
+}
+
+/// How a script started by [`spawn_runner`] is getting on.
+pub enum ScriptEvent {
+    Started(PathBuf),
+    /// The script ran to its end, taking the given time, with this many
+    /// failed assertions.
+    Finished(PathBuf, Duration, usize),
+    /// The script stopped on an error, which says where it happened.
+    Failed(PathBuf, String),
+}
+
+/// Starts the script runner thread. Every path sent on the returned channel
+/// is run on its own thread with a fresh engine, and how each run goes is
+/// sent on `report`.
+pub fn spawn_runner(host: ScriptHost, report: Sender<ScriptEvent>) -> Sender<PathBuf> {
+    let (tx, rx) = mpsc::channel::<PathBuf>();
+    let next_id = Arc::new(AtomicUsize::new(0)); // <- unique ID generator
+
+    thread::spawn(move || {
+        while let Ok(script) = rx.recv() {
+            let host = host.clone();
+            let next_id = Arc::clone(&next_id);
+            let report = report.clone();
+            thread::spawn(move || {
+                let failures = Arc::new(AtomicUsize::new(0));
+                let engine = new_engine(&host, next_id, Arc::clone(&failures));
+                let _ = report.send(ScriptEvent::Started(script.clone()));
+                let started = Instant::now();
+                let event = match engine.run_file(script.clone()) {
+                    Ok(_) => ScriptEvent::Finished(
+                        script,
+                        started.elapsed(),
+                        failures.load(Ordering::Relaxed),
+                    ),
+                    Err(e) => {
+                        host.errors.add(ErrorCategory::Script, 1);
+                        log::error!("Rhai error in {}: {}", script.display(), e);
+                        ScriptEvent::Failed(script, e.to_string())
+                    }
+                };
+                let _ = report.send(event);
+            });
+        }
+    });
+
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_read_does_not_consume_display() {
+        let rx_buffer = RxBuffer::new();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let buffer = rx_buffer.clone();
+        let script = thread::spawn(move || {
+            let mut engine = Engine::new();
+            register_uart_api(&mut engine, &buffer, mpsc::channel().0);
+            engine.register_fn("ready", move || ready_tx.send(()).unwrap());
+            // The first read subscribes, so the data comes after it.
+            engine
+                .eval::<String>("uart_read(0); ready(); uart_read_line(5000)")
+                .unwrap()
+        });
+
+        ready_rx.recv().unwrap();
+        rx_buffer.receive(b"first ");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rx_buffer.lock().bytes, b"first ");
+        rx_buffer.receive(b"line\r\nsecond\n");
+
+        assert_eq!(script.join().unwrap(), "first line");
+        assert_eq!(rx_buffer.lock().bytes, b"first line\r\nsecond\n");
+    }
+}
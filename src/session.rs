@@ -0,0 +1,621 @@
+use crate::buffer::RxBuffer;
+use crate::capture::{CaptureEntry, Direction};
+use crate::sync::lock;
+use crate::timeline::Timeline;
+use crate::traffic::Traffic;
+use crate::transport::{self, Transport, TransportKind};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The reader hands received data on once this much has piled up, even if
+/// the flush interval hasn't passed yet.
+const FLUSH_BYTES: usize = 4096;
+
+/// Limits and default for [`PortSettings::read_chunk`].
+pub const MIN_READ_CHUNK: usize = 64;
+pub const MAX_READ_CHUNK: usize = 4096;
+pub const DEFAULT_READ_CHUNK: usize = 128;
+
+/// Everything needed to open a port and run its reader.
+#[derive(Clone, Debug)]
+pub struct PortSettings {
+    pub transport: TransportKind,
+    /// The serial port, or `host:port` for TCP.
+    pub port_name: String,
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    /// How long the reader may collect data before passing it on. Zero hands
+    /// on every read as it comes.
+    pub flush_interval: Duration,
+    /// How long a single read waits for data.
+    pub read_timeout: Duration,
+    /// How long the reader sleeps after a read that returned nothing.
+    pub poll_interval: Duration,
+    /// Most bytes taken from the port per read, between [`MIN_READ_CHUNK`]
+    /// and [`MAX_READ_CHUNK`]. Small reads pass data on sooner, large ones
+    /// keep up with fast lines at less overhead.
+    pub read_chunk: usize,
+}
+
+impl PortSettings {
+    pub fn new(port_name: impl Into<String>, baud_rate: u32) -> Self {
+        Self {
+            transport: TransportKind::Serial,
+            port_name: port_name.into(),
+            baud_rate,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            flush_interval: Duration::from_millis(10),
+            read_timeout: Duration::from_millis(100),
+            poll_interval: Duration::from_millis(10),
+            read_chunk: DEFAULT_READ_CHUNK,
+        }
+    }
+
+    /// Opens the port directly, without a session around it.
+    pub fn open_port(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        if self.baud_rate == 0 {
+            return Err(serialport::Error::new(
+                serialport::ErrorKind::InvalidInput,
+                "baud rate must be non-zero",
+            ));
+        }
+        serialport::new(&self.port_name, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+            .timeout(self.read_timeout)
+            .open()
+    }
+
+    /// Opens whatever `transport` says, for use by a session.
+    pub fn open_transport(&self) -> serialport::Result<Box<dyn Transport>> {
+        Ok(match self.transport {
+            TransportKind::Serial => Box::new(self.open_port()?),
+            TransportKind::Tcp => {
+                Box::new(transport::connect_tcp(&self.port_name, self.read_timeout)?)
+            }
+        })
+    }
+}
+
+/// Describes why `port_name` could not be opened, with a hint for the usual
+/// causes.
+pub fn open_error_message(port_name: &str, e: &serialport::Error) -> String {
+    let description = e.description.to_lowercase();
+    let hint = match e.kind {
+        serialport::ErrorKind::Io(ErrorKind::PermissionDenied) => {
+            Some("Permission denied — add your user to the dialout group")
+        }
+        // Linux reports EBUSY, Windows "access is denied" for a port in use.
+        _ if description.contains("busy") || description.contains("access is denied") => {
+            Some("Port is busy — another program has it open")
+        }
+        serialport::ErrorKind::NoDevice | serialport::ErrorKind::Io(ErrorKind::NotFound) => {
+            Some("Port not found — is the device plugged in?")
+        }
+        _ => None,
+    };
+    match hint {
+        Some(hint) => format!("Failed to open {}: {} ({})", port_name, hint, e),
+        None => format!("Failed to open {}: {}", port_name, e),
+    }
+}
+
+/// Notifications sent by the reader thread to every subscriber.
+#[derive(Clone, Debug)]
+pub enum SessionEvent {
+    /// A chunk of bytes as read from the port.
+    Data(Vec<u8>),
+    /// The reader stopped because of an error that left the port unusable.
+    Closed(String),
+    /// A framing, parity or overrun error; the reader keeps going. `marker`
+    /// names it for display at `offset`, the stream position of the received
+    /// data (counting dropped bytes) where it happened.
+    LineError {
+        message: String,
+        marker: &'static str,
+        offset: u64,
+    },
+    /// Bytes that were written to the port, exactly as they went out.
+    Sent(Vec<u8>),
+    /// A queued write could not be completed. `device_gone` is set when the
+    /// error means the port is no longer usable.
+    WriteError { message: String, device_gone: bool },
+}
+
+/// Line-control requests for an open session, e.g. from scripts.
+#[derive(Clone, Debug)]
+pub enum PortOp {
+    SetDtr(bool),
+    SetRts(bool),
+    /// Holds the line in break for the given time.
+    Break(Duration),
+    /// Switches the open port to another baud rate.
+    SetBaud(u32),
+    /// Waits until everything queued for sending so far is on the wire.
+    Flush,
+}
+
+type Subscribers = Arc<Mutex<Vec<Sender<SessionEvent>>>>;
+
+/// How traffic is written to a log file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LogFormat {
+    /// Received bytes exactly as they came.
+    #[default]
+    Raw,
+    /// A [`CaptureEntry`] line per chunk sent or received, which keeps the
+    /// timing and can be replayed.
+    Timestamped,
+}
+
+/// A file that traffic is logged to, shared with the reader and writer.
+#[derive(Clone)]
+pub struct LogFile {
+    file: Arc<Mutex<File>>,
+    format: LogFormat,
+}
+
+impl LogFile {
+    pub fn new(file: File, format: LogFormat) -> Self {
+        Self {
+            file: Arc::new(Mutex::new(file)),
+            format,
+        }
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    fn record(&self, dir: Direction, data: &[u8]) -> io::Result<()> {
+        let mut file = lock(&self.file);
+        match self.format {
+            LogFormat::Raw if dir == Direction::Rx => file.write_all(data),
+            LogFormat::Raw => Ok(()),
+            LogFormat::Timestamped => {
+                let entry = CaptureEntry {
+                    time: Local::now().fixed_offset(),
+                    dir,
+                    data: data.to_vec(),
+                };
+                writeln!(file, "{}", entry.to_line())
+            }
+        }
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        lock(&self.file).sync_all()
+    }
+}
+
+/// An open serial port (or other [`Transport`]) with a background reader
+/// filling an [`RxBuffer`].
+///
+/// Dropping the session stops the reader, which releases the port so it can
+/// be opened again.
+pub struct SerialSession {
+    /// Shared by the writer and line control; the reader has its own handle.
+    port: Arc<Mutex<Box<dyn Transport>>>,
+    settings: Mutex<PortSettings>,
+    rx_buffer: RxBuffer,
+    subscribers: Subscribers,
+    writer: Writer,
+    log: Arc<Mutex<Option<LogFile>>>,
+    running: Arc<AtomicBool>,
+    reader: Option<JoinHandle<()>>,
+}
+
+/// Called by the writer thread once a flush is done.
+type FlushDone = Box<dyn FnOnce(io::Result<()>) + Send>;
+
+/// Work for the writer thread, handled in the order it was queued.
+enum WriteJob {
+    /// Bytes to write, with the pause after each byte (zero writes them all
+    /// at once).
+    Data(Vec<u8>, Duration),
+    Flush(FlushDone),
+    /// Queued when the session is dropped, so the thread lets go of the port
+    /// even while other [`Writer`]s are still around.
+    Stop,
+}
+
+/// A cloneable handle that queues writes on a session from other threads.
+#[derive(Clone)]
+pub struct Writer {
+    jobs: Sender<WriteJob>,
+    /// Set once the session is dropped.
+    closed: Arc<AtomicBool>,
+}
+
+impl Writer {
+    /// Returns `false` once the session is gone.
+    pub fn write(&self, data: &[u8]) -> bool {
+        self.write_paced(data, Duration::ZERO)
+    }
+
+    /// Like [`write`](Self::write), but writes one byte at a time with `gap`
+    /// after each, for devices that can't keep up with the line rate.
+    pub fn write_paced(&self, data: &[u8], gap: Duration) -> bool {
+        !self.closed.load(Ordering::Relaxed)
+            && self.jobs.send(WriteJob::Data(data.to_vec(), gap)).is_ok()
+    }
+
+    /// Calls `done` once everything queued before it has been written and
+    /// flushed. It gets an error if a write since the last flush failed.
+    /// `done` is dropped without being called if the session is gone.
+    pub fn flush_then(&self, done: impl FnOnce(io::Result<()>) + Send + 'static) {
+        if !self.closed.load(Ordering::Relaxed) {
+            let _ = self.jobs.send(WriteJob::Flush(Box::new(done)));
+        }
+    }
+}
+
+impl SerialSession {
+    /// Opens the port and starts the reader thread, which appends everything
+    /// it receives to `rx_buffer`. Bytes read and written are counted in
+    /// `traffic` and recorded in `timeline`.
+    pub fn open(
+        settings: &PortSettings,
+        rx_buffer: RxBuffer,
+        traffic: Traffic,
+        timeline: Timeline,
+    ) -> serialport::Result<Self> {
+        let port = settings.open_transport()?;
+        // The reader has a handle of its own, so it never holds up writes.
+        let reader = port.try_clone()?;
+        let port = Arc::new(Mutex::new(port));
+        let subscribers = Subscribers::default();
+        let log = Arc::default();
+        let writer = spawn_writer(
+            Arc::clone(&port),
+            Arc::clone(&subscribers),
+            traffic.clone(),
+            timeline.clone(),
+            Arc::clone(&log),
+        );
+
+        let mut session = Self {
+            port,
+            settings: Mutex::new(settings.clone()),
+            rx_buffer,
+            subscribers,
+            writer,
+            log,
+            running: Arc::new(AtomicBool::new(true)),
+            reader: None,
+        };
+        session.reader = Some(session.spawn_reader(reader, traffic, timeline));
+        Ok(session)
+    }
+
+    fn spawn_reader(
+        &self,
+        mut port: Box<dyn Transport>,
+        traffic: Traffic,
+        timeline: Timeline,
+    ) -> JoinHandle<()> {
+        let rx_buffer = self.rx_buffer.clone();
+        let subscribers = Arc::clone(&self.subscribers);
+        let PortSettings {
+            flush_interval,
+            poll_interval,
+            read_chunk,
+            ..
+        } = self.settings();
+        let log = Arc::clone(&self.log);
+        let running = Arc::clone(&self.running);
+        thread::spawn(move || {
+            let _guard = ClosedOnPanic(Arc::clone(&subscribers), "reader");
+            let mut buf = vec![0u8; read_chunk.clamp(MIN_READ_CHUNK, MAX_READ_CHUNK)];
+            // Reads are collected here so the shared buffer is locked once per
+            // flush rather than once per read.
+            let mut pending = Vec::new();
+            let mut last_flush = Instant::now();
+            let flush = |pending: &mut Vec<u8>| {
+                if !pending.is_empty() {
+                    if let Some(file) = &*lock(&log) {
+                        let _ = file.record(Direction::Rx, pending);
+                    }
+                    rx_buffer.receive(pending);
+                    timeline.record(Direction::Rx, pending);
+                    broadcast(&subscribers, SessionEvent::Data(std::mem::take(pending)));
+                }
+            };
+            let closed = loop {
+                if !running.load(Ordering::Relaxed) {
+                    break None;
+                }
+                let idle = match port.read(&mut buf) {
+                    Ok(n) if n > 0 => {
+                        traffic.add_rx(n);
+                        pending.extend_from_slice(&buf[..n]);
+                        false
+                    }
+                    Ok(_) => {
+                        // No data, avoid hogging CPU
+                        thread::sleep(poll_interval);
+                        true
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::TimedOut => {
+                        // Timeout — expected
+                        thread::sleep(poll_interval);
+                        true
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => false,
+                    Err(e) => match line_error_marker(&e) {
+                        Some(marker) => {
+                            // The marker goes after whatever arrived before the error.
+                            flush(&mut pending);
+                            last_flush = Instant::now();
+                            let offset = {
+                                let rx = rx_buffer.lock();
+                                rx.dropped + rx.bytes.len() as u64
+                            };
+                            let message = e.to_string();
+                            broadcast(
+                                &subscribers,
+                                SessionEvent::LineError {
+                                    message,
+                                    marker,
+                                    offset,
+                                },
+                            );
+                            thread::sleep(poll_interval);
+                            true
+                        }
+                        None => break Some(e.to_string()),
+                    },
+                };
+                // An idle line flushes right away so nothing waits for more data.
+                if idle || pending.len() >= FLUSH_BYTES || last_flush.elapsed() >= flush_interval {
+                    flush(&mut pending);
+                    last_flush = Instant::now();
+                }
+            };
+            flush(&mut pending);
+            if let Some(reason) = closed {
+                broadcast(&subscribers, SessionEvent::Closed(reason));
+            }
+        })
+    }
+
+    /// Queues `data` for transmission without blocking the caller.
+    /// Outcomes are reported as [`SessionEvent::Sent`] or
+    /// [`SessionEvent::WriteError`].
+    /// Writes go out in the order they were queued.
+    pub fn write(&self, data: &[u8]) {
+        self.writer.write(data);
+    }
+
+    /// Queues `data` to go out one byte at a time with `gap` after each.
+    pub fn write_paced(&self, data: &[u8], gap: Duration) {
+        self.writer.write_paced(data, gap);
+    }
+
+    pub fn writer(&self) -> Writer {
+        self.writer.clone()
+    }
+
+    /// Blocks until everything queued so far has been written and flushed.
+    pub fn flush(&self) -> io::Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.writer.flush_then(move |result| {
+            let _ = tx.send(result);
+        });
+        rx.recv()
+            .unwrap_or_else(|_| Err(io::Error::new(ErrorKind::BrokenPipe, "session closed")))
+    }
+
+    /// Shuts the session down for good: waits up to `timeout` for queued
+    /// writes to go out, lets the reader finish with what it has and makes
+    /// sure the log file is on disk. Dropping the session instead stops it
+    /// without waiting for any of that.
+    pub fn close(mut self, timeout: Duration) -> io::Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.writer.flush_then(move |result| {
+            let _ = tx.send(result);
+        });
+        let flushed = rx.recv_timeout(timeout).unwrap_or_else(|_| {
+            Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "pending writes did not finish",
+            ))
+        });
+        self.running.store(false, Ordering::Relaxed);
+        // The reader notices within one read timeout.
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        if let Some(file) = &*lock(&self.log) {
+            file.sync()?;
+        }
+        flushed
+    }
+
+    /// Starts logging traffic to `file` in its format, or stops logging with
+    /// `None`.
+    pub fn set_log(&self, file: Option<LogFile>) {
+        *lock(&self.log) = file;
+    }
+
+    pub fn set_dtr(&self, level: bool) -> serialport::Result<()> {
+        lock(&self.port).set_dtr(level)
+    }
+
+    pub fn set_rts(&self, level: bool) -> serialport::Result<()> {
+        lock(&self.port).set_rts(level)
+    }
+
+    /// Starts a break of `duration` on a background thread. The port is only
+    /// locked to set and clear the break, so writes go on in between.
+    pub fn send_break(&self, duration: Duration) -> serialport::Result<()> {
+        lock(&self.port).set_break()?;
+        let port = Arc::clone(&self.port);
+        thread::spawn(move || {
+            thread::sleep(duration);
+            let _ = lock(&port).clear_break();
+        });
+        Ok(())
+    }
+
+    /// Changes the baud rate without reopening the port.
+    pub fn set_baud_rate(&self, baud_rate: u32) -> serialport::Result<()> {
+        lock(&self.port).set_baud_rate(baud_rate)?;
+        lock(&self.settings).baud_rate = baud_rate;
+        Ok(())
+    }
+
+    pub fn apply(&self, op: PortOp) -> serialport::Result<()> {
+        match op {
+            PortOp::SetDtr(level) => self.set_dtr(level),
+            PortOp::SetRts(level) => self.set_rts(level),
+            PortOp::Break(duration) => self.send_break(duration),
+            PortOp::SetBaud(rate) => self.set_baud_rate(rate),
+            PortOp::Flush => Ok(self.flush()?),
+        }
+    }
+
+    /// The settings the port currently runs with.
+    pub fn settings(&self) -> PortSettings {
+        lock(&self.settings).clone()
+    }
+
+    pub fn rx_buffer(&self) -> &RxBuffer {
+        &self.rx_buffer
+    }
+
+    /// Returns a receiver that gets every event from now on.
+    pub fn events(&self) -> Receiver<SessionEvent> {
+        let (tx, rx) = mpsc::channel();
+        lock(&self.subscribers).push(tx);
+        rx
+    }
+}
+
+impl Drop for SerialSession {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        // Transfers may still hold a Writer; they fail from here on.
+        self.writer.closed.store(true, Ordering::Relaxed);
+        let _ = self.writer.jobs.send(WriteJob::Stop);
+    }
+}
+
+/// Starts the thread that performs queued writes and flushes one after
+/// another. It ends when the session is dropped.
+fn spawn_writer(
+    port: Arc<Mutex<Box<dyn Transport>>>,
+    subscribers: Subscribers,
+    traffic: Traffic,
+    timeline: Timeline,
+    log: Arc<Mutex<Option<LogFile>>>,
+) -> Writer {
+    let (tx, rx) = mpsc::channel::<WriteJob>();
+    thread::spawn(move || {
+        let _guard = ClosedOnPanic(Arc::clone(&subscribers), "writer");
+        // The first write error since the last flush, reported by that flush.
+        let mut failed = None;
+        while let Ok(job) = rx.recv() {
+            let (data, gap) = match job {
+                WriteJob::Data(data, gap) => (data, gap),
+                WriteJob::Flush(done) => {
+                    let result = match failed.take() {
+                        Some(e) => Err(e),
+                        None => lock(&port).flush(),
+                    };
+                    done(result);
+                    continue;
+                }
+                WriteJob::Stop => break,
+            };
+            let result = if gap.is_zero() {
+                lock(&port).write_all(&data)
+            } else {
+                // The port is let go between bytes so line control isn't held up.
+                data.iter().try_for_each(|b| {
+                    lock(&port).write_all(std::slice::from_ref(b))?;
+                    thread::sleep(gap);
+                    Ok(())
+                })
+            };
+            match result {
+                Ok(()) => {
+                    traffic.add_tx(data.len());
+                    timeline.record(Direction::Tx, &data);
+                    if let Some(file) = &*lock(&log) {
+                        let _ = file.record(Direction::Tx, &data);
+                    }
+                    broadcast(&subscribers, SessionEvent::Sent(data));
+                }
+                Err(e) => {
+                    let device_gone = !matches!(
+                        e.kind(),
+                        ErrorKind::TimedOut | ErrorKind::Interrupted | ErrorKind::WouldBlock
+                    );
+                    let message = e.to_string();
+                    failed.get_or_insert(e);
+                    broadcast(
+                        &subscribers,
+                        SessionEvent::WriteError {
+                            message,
+                            device_gone,
+                        },
+                    );
+                }
+            }
+        }
+    });
+    Writer {
+        jobs: tx,
+        closed: Arc::default(),
+    }
+}
+
+/// The marker shown for read errors that only garbled some data, or `None`
+/// when the port is gone.
+fn line_error_marker(e: &io::Error) -> Option<&'static str> {
+    let message = e.to_string().to_lowercase();
+    if message.contains("framing") {
+        Some("<<FRAMING ERROR>>")
+    } else if message.contains("parity") {
+        Some("<<PARITY ERROR>>")
+    } else if message.contains("overrun") {
+        Some("<<OVERRUN>>")
+    } else {
+        None
+    }
+}
+
+/// Reports the session as closed if the thread holding it dies from a panic,
+/// so the UI doesn't keep waiting on a reader or writer that is gone.
+struct ClosedOnPanic(Subscribers, &'static str);
+
+impl Drop for ClosedOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let reason = format!("{} thread panicked", self.1);
+            broadcast(&self.0, SessionEvent::Closed(reason));
+        }
+    }
+}
+
+fn broadcast(subscribers: &Subscribers, event: SessionEvent) {
+    // Receivers that went away are dropped from the list.
+    lock(subscribers).retain(|tx| tx.send(event.clone()).is_ok());
+}
@@ -0,0 +1,8 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks `mutex`, carrying on with the data as it was if another thread
+/// panicked while holding it. Everything shared here stays usable after a
+/// half-finished update, and a dead reader must not take the UI with it.
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
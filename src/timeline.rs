@@ -0,0 +1,90 @@
+use crate::capture::Direction;
+use crate::sync::lock;
+use chrono::{DateTime, Local};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Most entries kept; the oldest are dropped first.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Longest entry; a line that runs on past this continues in a new one, so
+/// data without newlines can't grow a single entry forever.
+const MAX_ENTRY_BYTES: usize = 4096;
+
+/// One line of traffic in one direction, stamped with when it started.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub dir: Direction,
+    pub time: DateTime<Local>,
+    pub bytes: Vec<u8>,
+}
+
+impl LogEntry {
+    fn is_complete(&self) -> bool {
+        self.bytes.ends_with(b"\n") || self.bytes.len() >= MAX_ENTRY_BYTES
+    }
+}
+
+/// Sent and received data interleaved in the order it went over the wire,
+/// filled by a session's reader and writer threads.
+///
+/// Data is cut at newlines and every [`MAX_ENTRY_BYTES`]; an unfinished line
+/// is continued by more data in the same direction and ended by data in the
+/// other.
+#[derive(Clone, Default)]
+pub struct Timeline(Arc<Mutex<Vec<LogEntry>>>);
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, dir: Direction, bytes: &[u8]) {
+        let mut entries = lock(&self.0);
+        let time = Local::now();
+        for line in bytes.split_inclusive(|&b| b == b'\n') {
+            let mut piece = line;
+            while !piece.is_empty() {
+                let last = match entries.last_mut() {
+                    Some(last) if last.dir == dir && !last.is_complete() => last,
+                    _ => {
+                        entries.push(LogEntry {
+                            dir,
+                            time,
+                            bytes: Vec::new(),
+                        });
+                        entries.last_mut().unwrap()
+                    }
+                };
+                let room = MAX_ENTRY_BYTES - last.bytes.len();
+                let (now, rest) = piece.split_at(room.min(piece.len()));
+                last.bytes.extend_from_slice(now);
+                piece = rest;
+            }
+        }
+        let excess = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..excess);
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Vec<LogEntry>> {
+        lock(&self.0)
+    }
+
+    pub fn clear(&self) {
+        lock(&self.0).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_without_newlines_are_split() {
+        let timeline = Timeline::new();
+        timeline.record(Direction::Rx, &[b'a'; 5000]);
+        timeline.record(Direction::Rx, &[b'a'; 5000]);
+        timeline.record(Direction::Rx, b"\nok\n");
+        let lengths: Vec<usize> = timeline.lock().iter().map(|e| e.bytes.len()).collect();
+        assert_eq!(lengths, [4096, 4096, 1809, 3]);
+    }
+}
@@ -0,0 +1,21 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TimestampMode {
+    /// Time of day, `[12:03:44.512]`.
+    WallClock,
+    /// Seconds since `zero`, `[+3.250]`.
+    Relative,
+}
+
+/// Formats the prefix put in front of a received line.
+pub fn format_stamp(mode: TimestampMode, time: DateTime<Local>, zero: DateTime<Local>) -> String {
+    match mode {
+        TimestampMode::WallClock => format!("[{}] ", time.format("%H:%M:%S%.3f")),
+        TimestampMode::Relative => {
+            let ms = (time - zero).num_milliseconds().max(0);
+            format!("[+{}.{:03}] ", ms / 1000, ms % 1000)
+        }
+    }
+}
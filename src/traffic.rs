@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often [`RateMeter`] updates its estimate.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Bytes received and sent on a connection, counted by the session's reader
+/// and writer threads.
+#[derive(Clone, Default)]
+pub struct Traffic(Arc<[AtomicU64; 2]>);
+
+impl Traffic {
+    const RX: usize = 0;
+    const TX: usize = 1;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_rx(&self, n: usize) {
+        self.0[Self::RX].fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_tx(&self, n: usize) {
+        self.0[Self::TX].fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn rx(&self) -> u64 {
+        self.0[Self::RX].load(Ordering::Relaxed)
+    }
+
+    pub fn tx(&self) -> u64 {
+        self.0[Self::TX].load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        for counter in self.0.iter() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Turns a growing byte count into bytes per second, measured over the last
+/// [`RATE_WINDOW`].
+pub struct RateMeter {
+    since: Instant,
+    start_total: u64,
+    rate: f64,
+}
+
+impl Default for RateMeter {
+    fn default() -> Self {
+        Self {
+            since: Instant::now(),
+            start_total: 0,
+            rate: 0.0,
+        }
+    }
+}
+
+impl RateMeter {
+    /// Feeds the current `total` and returns the latest estimate. A total
+    /// lower than before, e.g. after a reset, starts over.
+    pub fn sample(&mut self, total: u64) -> f64 {
+        let now = Instant::now();
+        if total < self.start_total {
+            *self = Self {
+                start_total: total,
+                ..Self::default()
+            };
+        }
+        let elapsed = now - self.since;
+        if elapsed >= RATE_WINDOW {
+            self.rate = (total - self.start_total) as f64 / elapsed.as_secs_f64();
+            self.since = now;
+            self.start_total = total;
+        }
+        self.rate
+    }
+}
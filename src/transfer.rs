@@ -0,0 +1,231 @@
+use crate::session::Writer;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest wait before a timed send checks whether it was cancelled.
+const ABORT_POLL: Duration = Duration::from_millis(50);
+
+/// A simple stop-and-wait protocol: every block has to be acknowledged with
+/// `ack` before the next one goes out.
+#[derive(Clone, Debug)]
+pub struct HandshakeConfig {
+    pub block_size: usize,
+    pub ack: Vec<u8>,
+    pub timeout: Duration,
+    /// How often a block is sent again after its ACK timed out.
+    pub retries: u32,
+}
+
+pub enum TransferEvent {
+    Progress {
+        sent: usize,
+        total: usize,
+    },
+    Done {
+        sent: usize,
+    },
+    Failed {
+        sent: usize,
+        error: String,
+    },
+    /// Stopped on request before everything was sent. `sent` only counts
+    /// blocks that were written out in full.
+    Cancelled {
+        sent: usize,
+    },
+}
+
+/// Sends `data` block by block on a background thread, waiting for the ACK
+/// in `rx` after each one. Setting `abort` stops it before the next block.
+pub fn spawn_handshake_send(
+    data: Vec<u8>,
+    config: HandshakeConfig,
+    writer: Writer,
+    rx: Receiver<Vec<u8>>,
+    abort: Arc<AtomicBool>,
+) -> Receiver<TransferEvent> {
+    let (tx, events) = mpsc::channel();
+    thread::spawn(move || {
+        let total = data.len();
+        let mut sent = 0;
+        for (i, block) in data.chunks(config.block_size.max(1)).enumerate() {
+            if abort.load(Ordering::Relaxed) {
+                let _ = tx.send(TransferEvent::Cancelled { sent });
+                return;
+            }
+            let mut acked = false;
+            for _ in 0..=config.retries {
+                // Anything received before the block went out can't be its ACK.
+                while rx.try_recv().is_ok() {}
+                if let Err(error) = write_block(&writer, block) {
+                    let _ = tx.send(stopped(&abort, sent, error));
+                    return;
+                }
+                if wait_for(&rx, &config.ack, config.timeout) {
+                    acked = true;
+                    break;
+                }
+            }
+            if !acked {
+                let error = format!(
+                    "block {} not acknowledged after {} attempts",
+                    i,
+                    config.retries + 1
+                );
+                let _ = tx.send(TransferEvent::Failed { sent, error });
+                return;
+            }
+            sent += block.len();
+            if tx.send(TransferEvent::Progress { sent, total }).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(TransferEvent::Done { sent });
+    });
+    events
+}
+
+/// Streams `reader` to the port in blocks of `block_size` on a background
+/// thread, pausing `gap` after each. `total` is only used for progress. The
+/// last block may be shorter. Setting `abort` stops it before the next block.
+pub fn spawn_paced_send(
+    mut reader: impl Read + Send + 'static,
+    total: usize,
+    block_size: usize,
+    gap: Duration,
+    writer: Writer,
+    abort: Arc<AtomicBool>,
+) -> Receiver<TransferEvent> {
+    let (tx, events) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = vec![0u8; block_size.max(1)];
+        let mut sent = 0;
+        loop {
+            if abort.load(Ordering::Relaxed) {
+                let _ = tx.send(TransferEvent::Cancelled { sent });
+                return;
+            }
+            let n = match read_block(&mut reader, &mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    let error = format!("read failed: {}", e);
+                    let _ = tx.send(TransferEvent::Failed { sent, error });
+                    return;
+                }
+            };
+            if let Err(error) = write_block(&writer, &buffer[..n]) {
+                let _ = tx.send(stopped(&abort, sent, error));
+                return;
+            }
+            sent += n;
+            let total = total.max(sent);
+            if tx.send(TransferEvent::Progress { sent, total }).is_err() {
+                return;
+            }
+            thread::sleep(gap);
+        }
+        let _ = tx.send(TransferEvent::Done { sent });
+    });
+    events
+}
+
+/// Sends each chunk once its offset from the start has passed, reproducing
+/// the timing of a capture. Setting `abort` stops it, also while waiting.
+pub fn spawn_timed_send(
+    chunks: Vec<(Duration, Vec<u8>)>,
+    writer: Writer,
+    abort: Arc<AtomicBool>,
+) -> Receiver<TransferEvent> {
+    let (tx, events) = mpsc::channel();
+    thread::spawn(move || {
+        let total = chunks.iter().map(|(_, data)| data.len()).sum();
+        let start = Instant::now();
+        let mut sent = 0;
+        for (offset, data) in chunks {
+            loop {
+                if abort.load(Ordering::Relaxed) {
+                    let _ = tx.send(TransferEvent::Cancelled { sent });
+                    return;
+                }
+                let wait = offset.saturating_sub(start.elapsed());
+                if wait.is_zero() {
+                    break;
+                }
+                thread::sleep(wait.min(ABORT_POLL));
+            }
+            if let Err(error) = write_block(&writer, &data) {
+                let _ = tx.send(stopped(&abort, sent, error));
+                return;
+            }
+            sent += data.len();
+            if tx.send(TransferEvent::Progress { sent, total }).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(TransferEvent::Done { sent });
+    });
+    events
+}
+
+/// Writes `data` and waits until it has left the port, so progress counts
+/// what was actually sent rather than what was queued.
+fn write_block(writer: &Writer, data: &[u8]) -> Result<(), String> {
+    if !writer.write(data) {
+        return Err("port closed".to_string());
+    }
+    let (done_tx, done) = mpsc::channel();
+    writer.flush_then(move |result| {
+        let _ = done_tx.send(result);
+    });
+    match done.recv() {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(format!("write failed: {}", e)),
+        Err(_) => Err("port closed".to_string()),
+    }
+}
+
+/// How a transfer ends when a block couldn't be written: a disconnect asked
+/// for by the user cancels it, anything else is a failure.
+fn stopped(abort: &AtomicBool, sent: usize, error: String) -> TransferEvent {
+    if abort.load(Ordering::Relaxed) {
+        TransferEvent::Cancelled { sent }
+    } else {
+        TransferEvent::Failed { sent, error }
+    }
+}
+
+/// Fills `buffer` as far as the reader allows. Returns less than its length
+/// only at the end of the input.
+fn read_block(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Waits until `pattern` shows up in the received stream.
+fn wait_for(rx: &Receiver<Vec<u8>>, pattern: &[u8], timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut seen = Vec::new();
+    loop {
+        if pattern.is_empty() || seen.windows(pattern.len()).any(|w| w == pattern) {
+            return true;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(left) {
+            Ok(chunk) => seen.extend(chunk),
+            Err(_) => return false,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use serialport::SerialPort;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long connecting to a TCP endpoint may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TransportKind {
+    #[default]
+    Serial,
+    /// A raw TCP socket, e.g. a serial-to-Ethernet gateway. The port name is
+    /// the `host:port` to connect to.
+    Tcp,
+}
+
+/// What a session reads from and writes to.
+///
+/// `read` returns `Ok(0)` or a `TimedOut` error when nothing arrived in time,
+/// and any other error once the link is gone. Line control only exists on
+/// serial ports; elsewhere it fails with `Unsupported`.
+pub trait Transport: Send {
+    /// A second handle to the same link, so reads don't have to wait for
+    /// writes and the other way round.
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>;
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Waits until everything written has left the local side.
+    fn flush(&mut self) -> io::Result<()>;
+
+    fn set_dtr(&mut self, _level: bool) -> serialport::Result<()> {
+        Err(unsupported("DTR"))
+    }
+
+    fn set_rts(&mut self, _level: bool) -> serialport::Result<()> {
+        Err(unsupported("RTS"))
+    }
+
+    fn set_break(&mut self) -> serialport::Result<()> {
+        Err(unsupported("break"))
+    }
+
+    fn clear_break(&mut self) -> serialport::Result<()> {
+        Err(unsupported("break"))
+    }
+
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+        Err(unsupported("baud rate"))
+    }
+}
+
+fn unsupported(what: &str) -> serialport::Error {
+    serialport::Error::new(
+        serialport::ErrorKind::Io(ErrorKind::Unsupported),
+        format!("{} is not available on this connection", what),
+    )
+}
+
+impl Transport for Box<dyn SerialPort> {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(SerialPort::try_clone(self.as_ref())?))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(self, buf)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        Write::write_all(self, data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(self)
+    }
+
+    fn set_dtr(&mut self, level: bool) -> serialport::Result<()> {
+        self.write_data_terminal_ready(level)
+    }
+
+    fn set_rts(&mut self, level: bool) -> serialport::Result<()> {
+        self.write_request_to_send(level)
+    }
+
+    fn set_break(&mut self) -> serialport::Result<()> {
+        SerialPort::set_break(self.as_ref())
+    }
+
+    fn clear_break(&mut self) -> serialport::Result<()> {
+        SerialPort::clear_break(self.as_ref())
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        SerialPort::set_baud_rate(self.as_mut(), baud_rate)
+    }
+}
+
+impl Transport for TcpStream {
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>> {
+        Ok(Box::new(TcpStream::try_clone(self)?))
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match Read::read(self, buf) {
+            Ok(0) => Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "connection closed by peer",
+            )),
+            // Unix reports an expired read timeout as WouldBlock.
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Err(ErrorKind::TimedOut.into()),
+            result => result,
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        Write::write_all(self, data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(self)
+    }
+}
+
+/// Connects to `address` (`host:port`), with reads giving up after
+/// `read_timeout`.
+pub fn connect_tcp(address: &str, read_timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(ErrorKind::InvalidInput, "no address to connect to");
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(read_timeout))?;
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
@@ -0,0 +1,119 @@
+use crate::hex::parse_escaped;
+use crate::sync::lock;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A line without a newline for this long is given up on.
+const MAX_LINE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TriggerAction {
+    /// Sends the text, after [`parse_escaped`].
+    Send(String),
+    RunScript(PathBuf),
+    /// Only reports the match.
+    Notify,
+}
+
+/// Runs `action` whenever a received line contains `pattern`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trigger {
+    pub enabled: bool,
+    pub pattern: String,
+    pub action: TriggerAction,
+}
+
+/// A trigger that went off: the index of its rule, the line so far and what
+/// became of the action.
+pub struct Fired {
+    pub rule: usize,
+    pub line: String,
+    pub result: Result<(), String>,
+}
+
+/// Where triggered actions are delivered.
+pub struct TriggerSinks {
+    pub uart_tx: Sender<Vec<u8>>,
+    pub script_tx: Sender<PathBuf>,
+}
+
+/// The rules in force, shared with the matching thread.
+#[derive(Clone, Default)]
+pub struct TriggerTable(Arc<Mutex<Vec<Trigger>>>);
+
+impl TriggerTable {
+    pub fn set(&self, rules: Vec<Trigger>) {
+        *lock(&self.0) = rules;
+    }
+}
+
+/// Tracks the line being received and which rules already fired on it.
+///
+/// The line is checked every time data arrives rather than only once it is
+/// complete, so prompts that wait for input without ending the line still
+/// match. Each rule fires at most once per line.
+#[derive(Default)]
+struct LineMatcher {
+    line: Vec<u8>,
+    fired: Vec<usize>,
+}
+
+impl LineMatcher {
+    fn feed(&mut self, bytes: &[u8], rules: &[Trigger], mut on_match: impl FnMut(usize, &str)) {
+        for piece in bytes.split_inclusive(|&b| b == b'\n') {
+            self.line.extend_from_slice(piece);
+            let text = String::from_utf8_lossy(&self.line);
+            let text = text.trim_end_matches(['\r', '\n']);
+            for (i, rule) in rules.iter().enumerate() {
+                if rule.enabled
+                    && !rule.pattern.is_empty()
+                    && !self.fired.contains(&i)
+                    && text.contains(&rule.pattern)
+                {
+                    self.fired.push(i);
+                    on_match(i, text);
+                }
+            }
+            if self.line.ends_with(b"\n") || self.line.len() >= MAX_LINE {
+                self.line.clear();
+                self.fired.clear();
+            }
+        }
+    }
+}
+
+/// Starts matching `feed` against the rules in `table` on a thread of its
+/// own. Every match is reported on the returned channel.
+pub fn spawn(table: TriggerTable, feed: Receiver<Vec<u8>>, sinks: TriggerSinks) -> Receiver<Fired> {
+    let (fired_tx, fired_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut matcher = LineMatcher::default();
+        while let Ok(bytes) = feed.recv() {
+            let rules = lock(&table.0).clone();
+            matcher.feed(&bytes, &rules, |rule, line| {
+                let result = match &rules[rule].action {
+                    TriggerAction::Send(text) => parse_escaped(text).and_then(|data| {
+                        sinks
+                            .uart_tx
+                            .send(data)
+                            .map_err(|_| "sending has stopped".to_string())
+                    }),
+                    TriggerAction::RunScript(path) => sinks
+                        .script_tx
+                        .send(path.clone())
+                        .map_err(|_| "the script runner has stopped".to_string()),
+                    TriggerAction::Notify => Ok(()),
+                };
+                let _ = fired_tx.send(Fired {
+                    rule,
+                    line: line.to_string(),
+                    result,
+                });
+            });
+        }
+    });
+    fired_rx
+}